@@ -1,5 +1,9 @@
+use std::process::Command;
+
 use serde::{Deserialize, Serialize};
 
+use crate::utils::on_windows;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Action {
     pub action_type: ActionType,
@@ -8,6 +12,7 @@ pub struct Action {
     pub copy: Option<CopyAction>,
     pub extension: Option<ExtensionAction>,
     pub dialog: Option<DialogAction>,
+    pub run_command: Option<RunCommandAction>,
     pub ignore: bool,
     pub ask_confirmation: bool,
 }
@@ -21,6 +26,7 @@ impl Action {
             copy: None,
             extension: None,
             dialog: None,
+            run_command: None,
             ignore: false,
             ask_confirmation: false,
         }
@@ -34,6 +40,7 @@ impl Action {
             copy: None,
             extension: None,
             dialog: None,
+            run_command: None,
             ignore: false,
             ask_confirmation: false,
         }
@@ -47,6 +54,7 @@ impl Action {
             copy: Some(action),
             extension: None,
             dialog: None,
+            run_command: None,
             ignore: false,
             ask_confirmation: false,
         }
@@ -60,6 +68,7 @@ impl Action {
             copy: None,
             extension: Some(action),
             dialog: None,
+            run_command: None,
             ignore: false,
             ask_confirmation: false,
         }
@@ -73,6 +82,21 @@ impl Action {
             copy: None,
             extension: None,
             dialog: Some(action),
+            run_command: None,
+            ignore: false,
+            ask_confirmation: false,
+        }
+    }
+
+    pub fn new_run_command(action: RunCommandAction) -> Self {
+        Self {
+            action_type: ActionType::RunCommand,
+            open_app: None,
+            open_url: None,
+            copy: None,
+            extension: None,
+            dialog: None,
+            run_command: Some(action),
             ignore: false,
             ask_confirmation: false,
         }
@@ -86,6 +110,7 @@ impl Action {
             copy: None,
             extension: None,
             dialog: None,
+            run_command: None,
             ignore: true,
             ask_confirmation: false,
         }
@@ -105,6 +130,7 @@ pub enum ActionType {
     Extension,
     Dialog,
     Ignore,
+    RunCommand,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -140,6 +166,45 @@ impl CopyAction {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunCommandAction {
+    pub command: String,
+    pub working_dir: Option<String>,
+}
+
+impl RunCommandAction {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            working_dir: None,
+        }
+    }
+
+    pub fn working_dir(&mut self, working_dir: impl Into<String>) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self.to_owned()
+    }
+
+    // The command runs through the shell, "sh -c" on Linux and "cmd /C" on Windows
+    pub fn get_command(&self) -> Command {
+        let mut command = if on_windows() {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(&self.command);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.command);
+            command
+        };
+
+        if let Some(working_dir) = &self.working_dir {
+            command.current_dir(working_dir);
+        }
+
+        command
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExtensionAction {
     pub extension_id: String,
@@ -359,7 +424,7 @@ impl ToggleField {
         description: impl Into<String>,
     ) -> Self {
         Self {
-            default_value: default_value.into(),
+            default_value,
             title: title.into(),
             description: description.into(),
         }
@@ -1,12 +1,256 @@
-use std::fs;
+use std::{fs, io};
 
-use crate::{indexing::App, paths::get_indexing_apps_path};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::WhiskersError,
+    indexing::App,
+    paths::{get_indexing_apps_path, get_recent_apps_path},
+    protocol::{decode, encode, Versioned},
+    storage::{lock_exclusive, write_atomic},
+    utils::get_timestamp,
+};
+
+pub const DEFAULT_MAX_RECENT_APPS: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecentApps {
+    pub max_entries: usize,
+    pub entries: Vec<RecentApp>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecentApp {
+    pub app_id: String,
+    pub launched_at: u64,
+}
+
+impl Default for RecentApps {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_RECENT_APPS,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Versioned for RecentApps {}
+
+pub fn try_get_apps() -> Result<Vec<App>, WhiskersError> {
+    let bytes = fs::read(get_indexing_apps_path())?;
+    decode(&bytes)
+}
 
 pub fn get_apps() -> Vec<App> {
-    let bytes = fs::read(get_indexing_apps_path()).expect("Error reading indexing apps");
+    match try_get_apps() {
+        Ok(apps) => apps,
+        Err(WhiskersError::Decode(_)) => Vec::new(),
+        Err(error) => panic!("Error reading indexing apps: {}", error),
+    }
+}
+
+// Moves the app to the top of the history, dropping the oldest launches past the maximum
+pub fn try_record_app_launch(app_id: impl Into<String>) -> Result<(), WhiskersError> {
+    let app_id = app_id.into();
+    let path = get_recent_apps_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut recent_apps = read_recent_apps()?;
+    recent_apps.entries.retain(|entry| entry.app_id != app_id);
+    recent_apps.entries.insert(
+        0,
+        RecentApp {
+            app_id,
+            launched_at: get_timestamp(),
+        },
+    );
+    recent_apps.entries.truncate(recent_apps.max_entries);
+
+    write_atomic(&path, encode(&recent_apps)?)
+}
+
+pub fn record_app_launch(app_id: impl Into<String>) {
+    try_record_app_launch(app_id).expect("Error recording app launch");
+}
+
+// The most recently launched apps that are still installed. Apps that were uninstalled are
+// evicted from the history.
+pub fn try_get_recent_apps(limit: usize) -> Result<Vec<App>, WhiskersError> {
+    let path = get_recent_apps_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut recent_apps = read_recent_apps()?;
+    let apps = match try_get_apps() {
+        Ok(apps) => apps,
+        Err(WhiskersError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new())
+        }
+        Err(error) => return Err(error),
+    };
+
+    let entries_count = recent_apps.entries.len();
+
+    recent_apps
+        .entries
+        .retain(|entry| apps.iter().any(|app| app.id == entry.app_id));
+
+    if recent_apps.entries.len() != entries_count {
+        write_atomic(&path, encode(&recent_apps)?)?;
+    }
+
+    Ok(recent_apps
+        .entries
+        .iter()
+        .filter_map(|entry| apps.iter().find(|app| app.id == entry.app_id))
+        .take(limit)
+        .cloned()
+        .collect())
+}
+
+pub fn get_recent_apps(limit: usize) -> Vec<App> {
+    try_get_recent_apps(limit).expect("Error getting recent apps")
+}
+
+pub fn try_get_recent_apps_history() -> Result<RecentApps, WhiskersError> {
+    let _lock = lock_exclusive(get_recent_apps_path())?;
+    read_recent_apps()
+}
+
+pub fn get_recent_apps_history() -> RecentApps {
+    try_get_recent_apps_history().expect("Error getting recent apps history")
+}
+
+pub fn try_set_max_recent_apps(max_entries: usize) -> Result<(), WhiskersError> {
+    let path = get_recent_apps_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut recent_apps = read_recent_apps()?;
+    recent_apps.max_entries = max_entries;
+    recent_apps.entries.truncate(max_entries);
+
+    write_atomic(&path, encode(&recent_apps)?)
+}
+
+pub fn set_max_recent_apps(max_entries: usize) {
+    try_set_max_recent_apps(max_entries).expect("Error setting max recent apps");
+}
+
+// Keeps the configured maximum
+pub fn try_clear_recent_apps() -> Result<(), WhiskersError> {
+    let path = get_recent_apps_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut recent_apps = read_recent_apps()?;
+    recent_apps.entries.clear();
+
+    write_atomic(&path, encode(&recent_apps)?)
+}
+
+pub fn clear_recent_apps() {
+    try_clear_recent_apps().expect("Error clearing recent apps");
+}
+
+// A missing or unreadable history starts over empty
+fn read_recent_apps() -> Result<RecentApps, WhiskersError> {
+    match fs::read(get_recent_apps_path()) {
+        Ok(bytes) => match decode(&bytes) {
+            Ok(recent_apps) => Ok(recent_apps),
+            Err(WhiskersError::Decode(_)) => Ok(RecentApps::default()),
+            Err(error) => Err(error),
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(RecentApps::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::set_test_home;
+
+    use super::*;
+
+    fn write_apps(ids: &[&str]) {
+        let apps: Vec<App> = ids
+            .iter()
+            .map(|id| App::new(*id, id.to_uppercase(), format!("/usr/bin/{}", id)))
+            .collect();
+
+        write_atomic(get_indexing_apps_path(), encode(&apps).unwrap()).unwrap();
+    }
+
+    fn get_history_ids() -> Vec<String> {
+        try_get_recent_apps_history()
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| entry.app_id)
+            .collect()
+    }
+
+    #[test]
+    fn oldest_launches_are_evicted_at_the_maximum() {
+        let _home = set_test_home("recent-apps-eviction");
+        try_set_max_recent_apps(3).unwrap();
+
+        for id in ["a", "b", "c", "d"] {
+            try_record_app_launch(id).unwrap();
+        }
+
+        assert_eq!(get_history_ids(), ["d", "c", "b"]);
+
+        // Launching an app again moves it to the top instead of adding a second entry
+        try_record_app_launch("b").unwrap();
+        assert_eq!(get_history_ids(), ["b", "d", "c"]);
+    }
+
+    #[test]
+    fn lowering_the_maximum_shrinks_the_history() {
+        let _home = set_test_home("recent-apps-set-max");
+
+        for id in ["a", "b", "c", "d"] {
+            try_record_app_launch(id).unwrap();
+        }
+
+        try_set_max_recent_apps(2).unwrap();
+
+        let history = try_get_recent_apps_history().unwrap();
+        assert_eq!(history.max_entries, 2);
+        assert_eq!(get_history_ids(), ["d", "c"]);
+
+        try_clear_recent_apps().unwrap();
+        assert!(get_history_ids().is_empty());
+        assert_eq!(try_get_recent_apps_history().unwrap().max_entries, 2);
+    }
+
+    #[test]
+    fn uninstalled_apps_are_dropped() {
+        let _home = set_test_home("recent-apps-uninstalled");
+        write_apps(&["a", "c"]);
+
+        for id in ["a", "b", "c"] {
+            try_record_app_launch(id).unwrap();
+        }
+
+        let ids: Vec<String> = try_get_recent_apps(10)
+            .unwrap()
+            .into_iter()
+            .map(|app| app.id)
+            .collect();
+
+        assert_eq!(ids, ["c", "a"]);
+        assert_eq!(get_history_ids(), ["c", "a"]);
+        assert_eq!(try_get_recent_apps(1).unwrap()[0].id, "c");
+    }
+
+    #[test]
+    fn unreadable_history_starts_over() {
+        let _home = set_test_home("recent-apps-unreadable");
+        write_atomic(get_recent_apps_path(), "not a history").unwrap();
 
-    match bincode::deserialize(&bytes) {
-        Ok(apps_indexing) => apps_indexing,
-        Err(_) => Vec::new(),
+        assert!(get_history_ids().is_empty());
+        assert_eq!(
+            try_get_recent_apps_history().unwrap().max_entries,
+            DEFAULT_MAX_RECENT_APPS
+        );
     }
 }
@@ -1,19 +1,48 @@
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf, process::exit};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{exit, Command},
+    sync::atomic::{AtomicU16, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 use walkdir::WalkDir;
 
 use crate::{
-    action::DialogAction,
+    action::{DialogAction, FieldType},
+    error::{decode_error, WhiskersError},
     extension::Extension,
     paths::{
-        get_dialog_request_path, get_extension_request_path, get_extension_response_path,
-        get_extensions_dir, get_indexing_extensions_path,
+        get_dialog_request_path, get_dialog_response_path, get_extension_request_json_path,
+        get_extension_request_path, get_extension_response_json_path, get_extension_response_path,
+        get_extensions_dir, get_indexing_extensions_path, get_session_dialog_request_path,
+        get_session_dialog_response_path, get_session_extension_request_json_path,
+        get_session_extension_request_path, get_session_extension_response_json_path,
+        get_session_extension_response_path,
     },
+    protocol::{decode, encode, negotiate_version, WireFormat, PROTOCOL_VERSION, WIRE_FORMAT_ENV},
     result::WLResult,
-    settings::ExtensionSetting,
+    settings::{ExtensionSetting, FromSettingValue, SettingValue},
+    storage::{lock_exclusive, write_atomic},
+};
+
+use super::{
+    manifest::{parse_manifest, validate_manifest, ManifestDiagnostic},
+    sessions::{
+        apply_session, check_session_id, get_session_id, is_valid_session_id, try_create_session,
+    },
+    settings::{is_unreadable_settings, try_get_settings, try_update_settings},
 };
 
-use super::settings::{get_settings, write_settings};
+const DIALOG_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub const REQUEST_PATH_ENV: &str = "WHISKERS_REQUEST_PATH";
+pub const RESPONSE_PATH_ENV: &str = "WHISKERS_RESPONSE_PATH";
+
+// The version of the last request read, the response is written in it so that an older launcher
+// can read it
+static REQUEST_VERSION: AtomicU16 = AtomicU16::new(PROTOCOL_VERSION);
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExtensionRequest {
@@ -22,12 +51,14 @@ pub struct ExtensionRequest {
     pub extension_action: Option<String>,
     pub search_text: Option<String>,
     pub args: Option<Vec<String>>,
+    pub session_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ActionContext {
     ResultsRequest,
     RunAction,
+    DialogResponse,
 }
 
 impl ExtensionRequest {
@@ -38,6 +69,7 @@ impl ExtensionRequest {
             extension_action: None,
             search_text: None,
             args: None,
+            session_id: None,
         }
     }
 
@@ -52,7 +84,12 @@ impl ExtensionRequest {
     }
 
     pub fn args(&mut self, args: Vec<String>) -> Self {
-        self.args = Some(args.into());
+        self.args = Some(args);
+        self.to_owned()
+    }
+
+    pub fn session_id(&mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
         self.to_owned()
     }
 }
@@ -72,7 +109,7 @@ impl ExtensionResponse {
     }
 
     pub fn args(&mut self, args: Vec<String>) -> Self {
-        self.args = Some(args.into());
+        self.args = Some(args);
         self.to_owned()
     }
 }
@@ -84,6 +121,25 @@ pub struct DialogResponse {
 }
 
 impl DialogResponse {
+    pub fn new(results: Vec<DialogResult>) -> Self {
+        Self {
+            results,
+            args: None,
+        }
+    }
+
+    pub fn args(&mut self, args: Vec<String>) -> Self {
+        self.args = Some(args);
+        self.to_owned()
+    }
+
+    pub fn get_result(&self, field_id: impl Into<String>) -> Option<&DialogResult> {
+        let field_id = field_id.into();
+        self.results
+            .iter()
+            .find(|result| result.field_id == field_id)
+    }
+
     pub fn get_result_value(self, field_id: impl Into<String>) -> Option<String> {
         let field_id = field_id.into();
 
@@ -95,181 +151,683 @@ impl DialogResponse {
 
         None
     }
+
+    pub fn get_text(&self, field_id: impl Into<String>) -> Option<String> {
+        self.get_result(field_id)
+            .filter(|result| matches!(result.field_type, FieldType::Input | FieldType::TextArea))
+            .map(|result| result.field_value.to_owned())
+    }
+
+    pub fn get_bool(&self, field_id: impl Into<String>) -> Option<bool> {
+        self.get_result(field_id)
+            .filter(|result| matches!(result.field_type, FieldType::Toggle))
+            .and_then(|result| result.field_value.parse::<bool>().ok())
+    }
+
+    pub fn get_select(&self, field_id: impl Into<String>) -> Option<String> {
+        self.get_result(field_id)
+            .filter(|result| matches!(result.field_type, FieldType::Select))
+            .map(|result| result.field_value.to_owned())
+    }
+
+    pub fn get_path(&self, field_id: impl Into<String>) -> Option<PathBuf> {
+        self.get_result(field_id)
+            .filter(|result| matches!(result.field_type, FieldType::FilePicker))
+            .filter(|result| !result.field_value.is_empty())
+            .map(|result| PathBuf::from(&result.field_value))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DialogResult {
     pub field_id: String,
+    pub field_type: FieldType,
     pub field_value: String,
     pub args: Option<Vec<String>>,
 }
 
-pub fn index_extensions() {
-    let mut extensions = Vec::<Extension>::new();
-    let extensions_dir = get_extensions_dir();
-    let indexing_extensions_path = get_indexing_extensions_path();
-    let mut settings = get_settings();
+impl DialogResult {
+    pub fn new(
+        field_id: impl Into<String>,
+        field_type: FieldType,
+        field_value: impl Into<String>,
+    ) -> Self {
+        Self {
+            field_id: field_id.into(),
+            field_type,
+            field_value: field_value.into(),
+            args: None,
+        }
+    }
 
-    if !indexing_extensions_path.parent().unwrap().exists() {
-        fs::create_dir_all(&indexing_extensions_path.parent().unwrap())
-            .expect("Error creating directory");
+    pub fn args(&mut self, args: Vec<String>) -> Self {
+        self.args = Some(args);
+        self.to_owned()
     }
+}
 
-    if !extensions_dir.exists() {
-        fs::create_dir_all(&extensions_dir).expect("Error creating extensions directory");
+#[derive(Debug, Clone)]
+pub struct IndexingReport {
+    pub indexed: Vec<String>,
+    pub diagnostics: Vec<ManifestDiagnostic>,
+}
+
+impl IndexingReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error())
     }
+}
 
-    for entry in WalkDir::new(&extensions_dir) {
-        if entry.is_ok() {
-            let entry = entry.unwrap();
-            let name = entry.file_name();
+pub fn try_index_extensions() -> Result<IndexingReport, WhiskersError> {
+    let mut indexed = Vec::<(PathBuf, Extension)>::new();
+    let mut diagnostics = Vec::<ManifestDiagnostic>::new();
+    let extensions_dir = get_extensions_dir();
+    let indexing_extensions_path = get_indexing_extensions_path();
+
+    let _lock = lock_exclusive(&indexing_extensions_path)?;
 
-            if name == "manifest.json" {
-                let json =
-                    fs::read_to_string(entry.path()).expect("Error getting manifest content");
+    fs::create_dir_all(&extensions_dir)?;
 
-                if let Ok(extension) = serde_json::from_str::<Extension>(&json) {
-                    extensions.push(extension.to_owned());
+    let settings = try_get_settings()?;
 
-                    let has_keyword = settings
-                        .extensions
-                        .iter()
-                        .any(|es| es.extension_id == extension.id && es.setting_id == "keyword");
+    for entry in WalkDir::new(&extensions_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .flatten()
+    {
+        if entry.file_name() != "manifest.json" {
+            continue;
+        }
 
-                    if !has_keyword {
+        let validation = validate_manifest(entry.path(), &indexed, &settings);
+        let has_errors = validation
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error());
+
+        diagnostics.extend(validation.diagnostics);
+
+        if let (Some(extension), false) = (validation.extension, has_errors) {
+            indexed.push((entry.path().to_owned(), extension));
+        }
+    }
+
+    let extensions: Vec<Extension> = indexed
+        .into_iter()
+        .map(|(_, extension)| extension)
+        .collect();
+
+    try_update_settings(|settings| {
+        for extension in &extensions {
+            let has_keyword = settings
+                .extensions
+                .iter()
+                .any(|es| es.extension_id == extension.id && es.setting_id == "keyword");
+
+            if !has_keyword {
+                settings.extensions.push(ExtensionSetting {
+                    extension_id: extension.id.to_owned(),
+                    setting_id: String::from("keyword"),
+                    setting_value: SettingValue::Text(extension.keyword.to_owned()),
+                })
+            }
+
+            if let Some(extension_settings) = &extension.settings {
+                for extension_setting in extension_settings {
+                    let existing_setting = settings.extensions.iter_mut().find(|es| {
+                        es.extension_id == extension.id && es.setting_id == extension_setting.id
+                    });
+
+                    // Values migrated from older settings files are plain text until the
+                    // manifest tells which type they have
+                    if let Some(existing_setting) = existing_setting {
+                        existing_setting.setting_value = existing_setting
+                            .setting_value
+                            .to_owned()
+                            .coerce(&extension_setting.setting_type);
+                    } else {
                         settings.extensions.push(ExtensionSetting {
                             extension_id: extension.id.to_owned(),
-                            setting_id: String::from("keyword"),
-                            setting_value: extension.keyword.to_owned(),
+                            setting_id: extension_setting.id.to_owned(),
+                            setting_value: extension_setting.default_value.to_owned(),
                         })
                     }
-
-                    if let Some(extension_settings) = extension.settings {
-                        for extension_setting in extension_settings {
-                            let has_setting = settings.extensions.iter().any(|es| {
-                                es.extension_id == extension.id
-                                    && es.setting_id == extension_setting.id
-                            });
-
-                            if !has_setting {
-                                settings.extensions.push(ExtensionSetting {
-                                    extension_id: extension.id.to_owned(),
-                                    setting_id: extension_setting.id.to_owned(),
-                                    setting_value: extension_setting.default_value.to_owned(),
-                                })
-                            }
-                        }
-                    }
                 }
             }
         }
-    }
+    })?;
+
+    let bytes = encode(&extensions)?;
+    write_atomic(&indexing_extensions_path, bytes)?;
+
+    Ok(IndexingReport {
+        indexed: extensions
+            .iter()
+            .map(|extension| extension.id.to_owned())
+            .collect(),
+        diagnostics,
+    })
+}
 
-    write_settings(settings);
+pub fn index_extensions() -> IndexingReport {
+    try_index_extensions().expect("Error indexing extensions")
+}
 
-    let bytes = bincode::serialize(&extensions).expect("Error serializing extensions");
-    fs::write(&get_indexing_extensions_path(), &bytes).expect("Error writing extensions");
+pub fn try_get_extensions() -> Result<Vec<Extension>, WhiskersError> {
+    let bytes = fs::read(get_indexing_extensions_path())?;
+    decode(&bytes)
 }
 
 pub fn get_extensions() -> Vec<Extension> {
-    let path = get_indexing_extensions_path();
-    let bytes = fs::read(path).expect("Error reading extensions");
-
-    match bincode::deserialize(&bytes) {
+    match try_get_extensions() {
         Ok(extensions) => extensions,
-        Err(_) => Vec::new(),
+        Err(WhiskersError::Decode(_)) => Vec::new(),
+        Err(error) => panic!("Error reading extensions: {}", error),
     }
 }
 
+pub fn try_write_extension_request(request: ExtensionRequest) -> Result<(), WhiskersError> {
+    if let Some(session_id) = &request.session_id {
+        try_create_session(session_id)?;
+    }
+
+    let bytes = encode(&request)?;
+    let path = extension_request_path(request.session_id.as_deref(), WireFormat::Bincode);
+    write_atomic(path, bytes)?;
+    Ok(())
+}
+
 pub fn write_extension_request(request: ExtensionRequest) {
-    let bytes = bincode::serialize(&request).expect("Error serializing request");
-    fs::write(&get_extension_request_path(), &bytes).expect("Error writing request");
+    try_write_extension_request(request).expect("Error writing extension request");
+}
+
+// Writes the request in the format and protocol version the given extension understands
+pub fn try_write_extension_request_for(
+    request: ExtensionRequest,
+    extension: &Extension,
+) -> Result<(), WhiskersError> {
+    if let Some(session_id) = &request.session_id {
+        try_create_session(session_id)?;
+    }
+
+    let version = negotiate_version(extension.api_version)?;
+    let bytes = extension.protocol.encode_version(&request, version)?;
+    let path = extension_request_path(request.session_id.as_deref(), extension.protocol);
+    write_atomic(path, bytes)?;
+    Ok(())
+}
+
+pub fn write_extension_request_for(request: ExtensionRequest, extension: &Extension) {
+    try_write_extension_request_for(request, extension).expect("Error writing extension request");
+}
+
+pub fn try_get_extension_request() -> Result<ExtensionRequest, WhiskersError> {
+    let format = WireFormat::from_env();
+    let bytes = fs::read(extension_request_path(get_session_id().as_deref(), format))?;
+    let request = format.decode(&bytes)?;
+
+    REQUEST_VERSION.store(format.get_version(&bytes), Ordering::Relaxed);
+    Ok(request)
 }
 
 pub fn get_extension_request() -> ExtensionRequest {
-    let bytes = fs::read(get_extension_request_path()).expect("Error reading extension request");
-    let request = bincode::deserialize(&bytes).expect("Error deserializing extension request");
-    request
+    try_get_extension_request().expect("Error getting extension request")
+}
+
+pub fn try_write_extension_response(response: ExtensionResponse) -> Result<(), WhiskersError> {
+    let format = WireFormat::from_env();
+    let bytes = format.encode_version(&response, REQUEST_VERSION.load(Ordering::Relaxed))?;
+    write_atomic(
+        extension_response_path(get_session_id().as_deref(), format),
+        bytes,
+    )?;
+    Ok(())
 }
 
 pub fn write_extension_response(response: ExtensionResponse) {
-    let bytes = bincode::serialize(&response).expect("Error serializing response");
-    fs::write(&get_extension_response_path(), bytes).expect("Error writing extension response");
+    try_write_extension_response(response).expect("Error writing extension response");
+}
+
+pub fn try_get_extension_response() -> Result<ExtensionResponse, WhiskersError> {
+    let bytes = fs::read(get_extension_response_path())?;
+    decode(&bytes)
 }
 
 pub fn get_extension_response() -> ExtensionResponse {
-    let bytes = fs::read(get_extension_response_path()).expect("Error reading extension response");
-    let response = bincode::deserialize(&bytes).expect("Error deserializing extension response");
-    response
+    try_get_extension_response().expect("Error getting extension response")
+}
+
+pub fn try_get_session_extension_response(
+    session_id: impl Into<String>,
+) -> Result<ExtensionResponse, WhiskersError> {
+    let session_id = session_id.into();
+    check_session_id(&session_id)?;
+
+    let bytes = fs::read(get_session_extension_response_path(session_id))?;
+    decode(&bytes)
+}
+
+pub fn get_session_extension_response(session_id: impl Into<String>) -> ExtensionResponse {
+    try_get_session_extension_response(session_id).expect("Error getting extension response")
+}
+
+pub fn try_get_session_extension_response_for(
+    session_id: impl Into<String>,
+    extension: &Extension,
+) -> Result<ExtensionResponse, WhiskersError> {
+    let session_id = session_id.into();
+    check_session_id(&session_id)?;
+
+    let bytes = fs::read(extension_response_path(
+        Some(&session_id),
+        extension.protocol,
+    ))?;
+    extension.protocol.decode(&bytes)
+}
+
+pub fn get_session_extension_response_for(
+    session_id: impl Into<String>,
+    extension: &Extension,
+) -> ExtensionResponse {
+    try_get_session_extension_response_for(session_id, extension)
+        .expect("Error getting extension response")
+}
+
+// Sets up the environment an extension process needs to find its request and answer it. The
+// paths are mostly useful to extensions that aren't written in Rust.
+pub fn apply_extension_env<'a>(
+    command: &'a mut Command,
+    extension: &Extension,
+    session_id: impl Into<String>,
+) -> &'a mut Command {
+    let session_id = session_id.into();
+
+    // Extensions ignore invalid session ids and use the shared paths, so the paths match that
+    let session = Some(session_id.as_str()).filter(|session_id| is_valid_session_id(session_id));
+    let request_path = extension_request_path(session, extension.protocol);
+    let response_path = extension_response_path(session, extension.protocol);
+
+    if session.is_some() {
+        apply_session(command, &session_id);
+    }
+
+    command
+        .env(WIRE_FORMAT_ENV, extension.protocol.name())
+        .env(REQUEST_PATH_ENV, request_path)
+        .env(RESPONSE_PATH_ENV, response_path)
+}
+
+// The dialog files of the current session, like the extension request and response
+pub fn try_write_dialog_request(request: DialogAction) -> Result<(), WhiskersError> {
+    let session_id = get_session_id();
+
+    if let Some(session_id) = &session_id {
+        try_create_session(session_id)?;
+    }
+
+    let bytes = encode(&request)?;
+    write_atomic(dialog_request_path(session_id.as_deref()), bytes)?;
+    Ok(())
 }
 
 pub fn write_dialog_request(request: DialogAction) {
-    let bytes = bincode::serialize(&request).expect("Error serializing request");
-    fs::write(&get_dialog_request_path(), &bytes).expect("Error writing request");
+    try_write_dialog_request(request).expect("Error writing dialog request");
+}
+
+pub fn try_get_dialog_request() -> Result<DialogAction, WhiskersError> {
+    let bytes = fs::read(dialog_request_path(get_session_id().as_deref()))?;
+    decode(&bytes)
 }
 
 pub fn get_dialog_request() -> DialogAction {
-    let bytes = fs::read(get_dialog_request_path()).expect("Error reading dialog request");
-    let request = bincode::deserialize(&bytes).expect("Error deserializing dialog request");
-    request
+    try_get_dialog_request().expect("Error getting dialog request")
+}
+
+pub fn try_write_dialog_response(response: DialogResponse) -> Result<(), WhiskersError> {
+    let session_id = get_session_id();
+
+    if let Some(session_id) = &session_id {
+        try_create_session(session_id)?;
+    }
+
+    let bytes = encode(&response)?;
+    write_atomic(dialog_response_path(session_id.as_deref()), bytes)?;
+    Ok(())
 }
 
 pub fn write_dialog_response(response: DialogResponse) {
-    let bytes = bincode::serialize(&response).expect("Error serializing response");
-    fs::write(get_extension_response_path(), &bytes).expect("Error writing response");
+    try_write_dialog_response(response).expect("Error writing dialog response");
+}
+
+pub fn try_get_dialog_response() -> Result<DialogResponse, WhiskersError> {
+    let bytes = fs::read(dialog_response_path(get_session_id().as_deref()))?;
+    decode(&bytes)
 }
 
 pub fn get_dialog_response() -> DialogResponse {
-    let bytes = fs::read(get_extension_response_path()).expect("Error reading dialog response");
-    let response = bincode::deserialize(&bytes).expect("Error deserializing dialog response");
-    response
+    try_get_dialog_response().expect("Error getting dialog response")
 }
 
+// Sends the dialog to the dialog window and waits until it writes a response back
+pub fn try_run_dialog(
+    dialog: DialogAction,
+    timeout: Duration,
+) -> Result<DialogResponse, WhiskersError> {
+    wait_for_dialog(get_session_id().as_deref(), dialog, timeout)
+}
+
+pub fn run_dialog(dialog: DialogAction, timeout: Duration) -> DialogResponse {
+    try_run_dialog(dialog, timeout).expect("Error running dialog")
+}
+
+// Runs the dialog in the session's dir so dialogs of parallel invocations don't answer each
+// other. The dialog window has to be started with the session applied.
+pub fn try_run_session_dialog(
+    session_id: impl Into<String>,
+    dialog: DialogAction,
+    timeout: Duration,
+) -> Result<DialogResponse, WhiskersError> {
+    let session_id = session_id.into();
+    try_create_session(&session_id)?;
+
+    wait_for_dialog(Some(&session_id), dialog, timeout)
+}
+
+pub fn run_session_dialog(
+    session_id: impl Into<String>,
+    dialog: DialogAction,
+    timeout: Duration,
+) -> DialogResponse {
+    try_run_session_dialog(session_id, dialog, timeout).expect("Error running dialog")
+}
+
+fn wait_for_dialog(
+    session_id: Option<&str>,
+    dialog: DialogAction,
+    timeout: Duration,
+) -> Result<DialogResponse, WhiskersError> {
+    let response_path = dialog_response_path(session_id);
+
+    if response_path.exists() {
+        fs::remove_file(&response_path)?;
+    }
+
+    write_atomic(dialog_request_path(session_id), encode(&dialog)?)?;
+
+    let started_at = Instant::now();
+
+    while !response_path.exists() {
+        if started_at.elapsed() > timeout {
+            return Err(WhiskersError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for dialog response",
+            )));
+        }
+
+        thread::sleep(DIALOG_POLL_INTERVAL);
+    }
+
+    let response = decode(&fs::read(&response_path)?)?;
+    fs::remove_file(&response_path)?;
+
+    Ok(response)
+}
+
+pub fn try_send_response(results: Vec<WLResult>) -> Result<(), WhiskersError> {
+    try_write_extension_response(ExtensionResponse::new(results))
+}
+
+// Exits once the response is written since there's nothing left for a one shot extension to do
 pub fn send_response(results: Vec<WLResult>) {
-    let response = ExtensionResponse::new(results);
-    write_extension_response(response);
+    try_send_response(results).expect("Error sending response");
     exit(0);
 }
 
-pub fn get_extension_dir(extension_id: impl Into<String>) -> Option<PathBuf> {
+pub fn try_get_extension_dir(extension_id: impl Into<String>) -> Result<PathBuf, WhiskersError> {
     let extension_id = extension_id.into();
     let extensions_dir = get_extensions_dir();
 
-    for entry in WalkDir::new(&extensions_dir) {
-        if entry.is_ok() {
-            let entry = entry.unwrap();
-            let name = entry.file_name();
-
-            if name == "manifest.json" {
-                let json =
-                    fs::read_to_string(entry.path()).expect("Error getting manifest content");
+    for entry in WalkDir::new(&extensions_dir).into_iter().flatten() {
+        if entry.file_name() != "manifest.json" {
+            continue;
+        }
 
-                if let Ok(extension) = serde_json::from_str::<Extension>(&json) {
-                    if extension_id == extension.id {
-                        return Some(entry.path().parent().unwrap().to_owned());
-                    }
+        if let Ok(extension) = read_manifest(entry.path()) {
+            if extension_id == extension.id {
+                if let Some(parent) = entry.path().parent() {
+                    return Ok(parent.to_owned());
                 }
             }
         }
     }
 
-    return None;
+    Err(WhiskersError::MissingExtension(extension_id))
 }
 
-pub fn get_extension_setting(
+pub fn get_extension_dir(extension_id: impl Into<String>) -> Option<PathBuf> {
+    try_get_extension_dir(extension_id).ok()
+}
+
+pub fn try_get_extension_manifest(
+    extension_dir: impl AsRef<Path>,
+) -> Result<Extension, WhiskersError> {
+    read_manifest(&extension_dir.as_ref().join("manifest.json"))
+}
+
+pub fn try_get_extension_setting_value(
     extension_id: impl Into<String>,
     setting_id: impl Into<String>,
-) -> Option<String> {
+) -> Result<SettingValue, WhiskersError> {
     let setting_id = setting_id.into();
     let extension_id = extension_id.into();
 
-    let settings = get_settings();
+    let settings = try_get_settings()?;
 
     for setting in settings.extensions {
         if setting.extension_id == extension_id && setting.setting_id == setting_id {
-            return Some(setting.setting_value);
+            return Ok(setting.setting_value);
         }
     }
 
-    None
+    Err(WhiskersError::InvalidSetting {
+        extension_id,
+        setting_id,
+    })
+}
+
+pub fn get_extension_setting_value(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Option<SettingValue> {
+    match try_get_extension_setting_value(extension_id, setting_id) {
+        Ok(value) => Some(value),
+        Err(WhiskersError::InvalidSetting { .. }) => None,
+        Err(error) if is_unreadable_settings(&error) => None,
+        Err(error) => panic!("Error getting extension setting: {}", error),
+    }
+}
+
+pub fn try_get_extension_setting_as<T: FromSettingValue>(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Result<T, WhiskersError> {
+    let extension_id = extension_id.into();
+    let setting_id = setting_id.into();
+    let value = try_get_extension_setting_value(&extension_id, &setting_id)?;
+
+    T::from_setting_value(&value).ok_or(WhiskersError::InvalidSetting {
+        extension_id,
+        setting_id,
+    })
+}
+
+pub fn get_extension_setting_as<T: FromSettingValue>(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Option<T> {
+    match try_get_extension_setting_as(extension_id, setting_id) {
+        Ok(value) => Some(value),
+        Err(WhiskersError::InvalidSetting { .. }) => None,
+        Err(error) if is_unreadable_settings(&error) => None,
+        Err(error) => panic!("Error getting extension setting: {}", error),
+    }
+}
+
+pub fn try_get_extension_setting(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Result<String, WhiskersError> {
+    try_get_extension_setting_as::<String>(extension_id, setting_id)
+}
+
+pub fn get_extension_setting(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Option<String> {
+    match try_get_extension_setting(extension_id, setting_id) {
+        Ok(value) => Some(value),
+        Err(WhiskersError::InvalidSetting { .. }) => None,
+        Err(error) if is_unreadable_settings(&error) => None,
+        Err(error) => panic!("Error getting extension setting: {}", error),
+    }
+}
+
+fn extension_request_path(session_id: Option<&str>, format: WireFormat) -> PathBuf {
+    match (session_id, format) {
+        (Some(session_id), WireFormat::Bincode) => get_session_extension_request_path(session_id),
+        (Some(session_id), WireFormat::Json) => get_session_extension_request_json_path(session_id),
+        (None, WireFormat::Bincode) => get_extension_request_path(),
+        (None, WireFormat::Json) => get_extension_request_json_path(),
+    }
+}
+
+fn extension_response_path(session_id: Option<&str>, format: WireFormat) -> PathBuf {
+    match (session_id, format) {
+        (Some(session_id), WireFormat::Bincode) => get_session_extension_response_path(session_id),
+        (Some(session_id), WireFormat::Json) => {
+            get_session_extension_response_json_path(session_id)
+        }
+        (None, WireFormat::Bincode) => get_extension_response_path(),
+        (None, WireFormat::Json) => get_extension_response_json_path(),
+    }
+}
+
+fn dialog_request_path(session_id: Option<&str>) -> PathBuf {
+    match session_id {
+        Some(session_id) => get_session_dialog_request_path(session_id),
+        None => get_dialog_request_path(),
+    }
+}
+
+fn dialog_response_path(session_id: Option<&str>) -> PathBuf {
+    match session_id {
+        Some(session_id) => get_session_dialog_response_path(session_id),
+        None => get_dialog_response_path(),
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Extension, WhiskersError> {
+    if !path.exists() {
+        return Err(WhiskersError::MissingManifest(path.to_owned()));
+    }
+
+    let json = fs::read_to_string(path)?;
+    parse_manifest(&json).map_err(decode_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::sessions::{new_session_id, try_end_session};
+
+    use super::*;
+
+    const DIALOG_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn get_dialog() -> DialogAction {
+        DialogAction::new("notes", "add", "Add note", "Add", Vec::new())
+    }
+
+    // Stands in for the dialog window of the session, answering with the session id
+    fn answer_dialog(session_id: String) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let request_path = get_session_dialog_request_path(&session_id);
+            let started_at = Instant::now();
+
+            while !request_path.exists() && started_at.elapsed() < DIALOG_TIMEOUT {
+                thread::sleep(Duration::from_millis(5));
+            }
+
+            let response = DialogResponse::new(vec![DialogResult::new(
+                "session",
+                FieldType::Input,
+                &session_id,
+            )]);
+
+            write_atomic(
+                get_session_dialog_response_path(&session_id),
+                encode(&response).unwrap(),
+            )
+            .unwrap();
+        })
+    }
+
+    #[test]
+    fn parallel_session_dialogs_get_their_own_response() {
+        let sessions = [new_session_id(), new_session_id()];
+
+        let runs: Vec<_> = sessions
+            .iter()
+            .map(|session_id| {
+                let session_id = session_id.to_owned();
+                thread::spawn(move || {
+                    try_run_session_dialog(&session_id, get_dialog(), DIALOG_TIMEOUT).unwrap()
+                })
+            })
+            .collect();
+
+        let windows: Vec<_> = sessions.iter().cloned().map(answer_dialog).collect();
+
+        for (session_id, run) in sessions.iter().zip(runs) {
+            let response = run.join().unwrap();
+
+            assert_eq!(
+                response.get_result("session").unwrap().field_value,
+                *session_id
+            );
+            assert!(!get_session_dialog_response_path(session_id).exists());
+        }
+
+        for window in windows {
+            window.join().unwrap();
+        }
+
+        for session_id in sessions {
+            try_end_session(session_id).unwrap();
+        }
+    }
+
+    #[test]
+    fn unanswered_session_dialog_times_out() {
+        let session_id = new_session_id();
+
+        let result = try_run_session_dialog(&session_id, get_dialog(), Duration::from_millis(100));
+
+        assert!(matches!(
+            result,
+            Err(WhiskersError::Io(error)) if error.kind() == io::ErrorKind::TimedOut
+        ));
+        assert!(get_session_dialog_request_path(&session_id).exists());
+
+        try_end_session(session_id).unwrap();
+    }
+
+    #[test]
+    fn invalid_session_ids_are_refused() {
+        assert!(matches!(
+            try_run_session_dialog("../a", get_dialog(), DIALOG_TIMEOUT),
+            Err(WhiskersError::InvalidSession(_))
+        ));
+    }
 }
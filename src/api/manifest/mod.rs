@@ -0,0 +1,594 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    extension::{Extension, SettingType},
+    protocol::{is_supported_version, PROTOCOL_VERSION},
+    settings::Settings,
+};
+
+#[derive(Debug, Clone)]
+pub struct ManifestDiagnostic {
+    pub path: PathBuf,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum DiagnosticKind {
+    ParseError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    ReadError {
+        message: String,
+    },
+    DuplicateId {
+        extension_id: String,
+        first_path: PathBuf,
+    },
+    KeywordCollision {
+        extension_id: String,
+        keyword: String,
+        owner: KeywordOwner,
+    },
+    InvalidSelectDefault {
+        extension_id: String,
+        setting_id: String,
+        default_value: String,
+    },
+    UnknownShowCondition {
+        extension_id: String,
+        setting_id: String,
+        condition_setting_id: String,
+    },
+    UnsupportedApiVersion {
+        extension_id: String,
+        api_version: u16,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeywordOwner {
+    Extension(String),
+    SearchEngine(usize),
+    SearchKeyword,
+    Shortcut(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct ManifestValidation {
+    pub extension: Option<Extension>,
+    pub diagnostics: Vec<ManifestDiagnostic>,
+}
+
+impl ManifestDiagnostic {
+    // Errors keep the extension out of the index while anything else is only reported
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.kind,
+            DiagnosticKind::ParseError { .. }
+                | DiagnosticKind::ReadError { .. }
+                | DiagnosticKind::DuplicateId { .. }
+                | DiagnosticKind::UnsupportedApiVersion { .. }
+        )
+    }
+}
+
+impl fmt::Display for ManifestDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+
+        match &self.kind {
+            DiagnosticKind::ParseError { message, .. } => write!(f, "{}", message),
+            DiagnosticKind::ReadError { message } => write!(f, "{}", message),
+            DiagnosticKind::DuplicateId {
+                extension_id,
+                first_path,
+            } => write!(
+                f,
+                "extension id \"{}\" is already used by {}",
+                extension_id,
+                first_path.display()
+            ),
+            DiagnosticKind::KeywordCollision {
+                extension_id,
+                keyword,
+                owner,
+            } => write!(
+                f,
+                "keyword \"{}\" of extension \"{}\" is already used by {}",
+                keyword, extension_id, owner
+            ),
+            DiagnosticKind::InvalidSelectDefault {
+                extension_id,
+                setting_id,
+                default_value,
+            } => write!(
+                f,
+                "default value \"{}\" of setting \"{}\" in extension \"{}\" is not one of its select options",
+                default_value, setting_id, extension_id
+            ),
+            DiagnosticKind::UnknownShowCondition {
+                extension_id,
+                setting_id,
+                condition_setting_id,
+            } => write!(
+                f,
+                "setting \"{}\" in extension \"{}\" has a show condition on unknown setting \"{}\"",
+                setting_id, extension_id, condition_setting_id
+            ),
+            DiagnosticKind::UnsupportedApiVersion {
+                extension_id,
+                api_version,
+            } => write!(
+                f,
+                "extension \"{}\" requires api version {} but only up to {} is supported",
+                extension_id, api_version, PROTOCOL_VERSION
+            ),
+        }
+    }
+}
+
+impl fmt::Display for KeywordOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeywordOwner::Extension(extension_id) => write!(f, "extension \"{}\"", extension_id),
+            KeywordOwner::SearchEngine(id) => write!(f, "search engine {}", id),
+            KeywordOwner::SearchKeyword => write!(f, "the search keyword"),
+            KeywordOwner::Shortcut(id) => write!(f, "shortcut {}", id),
+        }
+    }
+}
+
+// Parses a manifest giving each setting default the type its setting type expects
+pub fn parse_manifest(json: &str) -> Result<Extension, serde_json::Error> {
+    let mut extension = serde_json::from_str::<Extension>(json)?;
+
+    if let Some(settings) = &mut extension.settings {
+        for setting in settings {
+            setting.default_value = setting
+                .default_value
+                .to_owned()
+                .coerce(&setting.setting_type);
+        }
+    }
+
+    Ok(extension)
+}
+
+// Validates a manifest against the extensions that were already accepted and the user settings
+pub fn validate_manifest(
+    path: impl AsRef<Path>,
+    indexed: &[(PathBuf, Extension)],
+    settings: &Settings,
+) -> ManifestValidation {
+    let path = path.as_ref().to_owned();
+
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(error) => {
+            return ManifestValidation {
+                extension: None,
+                diagnostics: vec![ManifestDiagnostic {
+                    path,
+                    kind: DiagnosticKind::ReadError {
+                        message: error.to_string(),
+                    },
+                }],
+            }
+        }
+    };
+
+    validate_manifest_str(path, &json, indexed, settings)
+}
+
+pub fn validate_manifest_str(
+    path: impl AsRef<Path>,
+    json: &str,
+    indexed: &[(PathBuf, Extension)],
+    settings: &Settings,
+) -> ManifestValidation {
+    let path = path.as_ref().to_owned();
+    let mut diagnostics = Vec::<ManifestDiagnostic>::new();
+
+    let extension = match parse_manifest(json) {
+        Ok(extension) => extension,
+        Err(error) => {
+            return ManifestValidation {
+                extension: None,
+                diagnostics: vec![ManifestDiagnostic {
+                    path,
+                    kind: DiagnosticKind::ParseError {
+                        line: error.line(),
+                        column: error.column(),
+                        message: error.to_string(),
+                    },
+                }],
+            }
+        }
+    };
+
+    let mut push = |kind: DiagnosticKind| {
+        diagnostics.push(ManifestDiagnostic {
+            path: path.to_owned(),
+            kind,
+        })
+    };
+
+    if !is_supported_version(extension.api_version) {
+        push(DiagnosticKind::UnsupportedApiVersion {
+            extension_id: extension.id.to_owned(),
+            api_version: extension.api_version,
+        });
+    }
+
+    if let Some((first_path, _)) = indexed.iter().find(|(_, other)| other.id == extension.id) {
+        push(DiagnosticKind::DuplicateId {
+            extension_id: extension.id.to_owned(),
+            first_path: first_path.to_owned(),
+        });
+    }
+
+    let keyword = get_extension_keyword(&extension, settings);
+    let other_extensions: Vec<Extension> = indexed
+        .iter()
+        .map(|(_, other)| other.to_owned())
+        .filter(|other| other.id != extension.id)
+        .collect();
+
+    if let Some(owner) = find_keyword_owner(&keyword, settings, &other_extensions) {
+        push(DiagnosticKind::KeywordCollision {
+            extension_id: extension.id.to_owned(),
+            keyword,
+            owner,
+        });
+    }
+
+    if let Some(extension_settings) = &extension.settings {
+        for setting in extension_settings {
+            if let SettingType::Select = setting.setting_type {
+                let options = setting.select_options.to_owned().unwrap_or_default();
+
+                let default_value = setting.default_value.to_string();
+
+                if !options.iter().any(|option| option.id == default_value) {
+                    push(DiagnosticKind::InvalidSelectDefault {
+                        extension_id: extension.id.to_owned(),
+                        setting_id: setting.id.to_owned(),
+                        default_value,
+                    });
+                }
+            }
+
+            for condition in setting.show_conditions.to_owned().unwrap_or_default() {
+                let is_known = extension_settings
+                    .iter()
+                    .any(|other| other.id == condition.setting_id);
+
+                if !is_known {
+                    push(DiagnosticKind::UnknownShowCondition {
+                        extension_id: extension.id.to_owned(),
+                        setting_id: setting.id.to_owned(),
+                        condition_setting_id: condition.setting_id.to_owned(),
+                    });
+                }
+            }
+        }
+    }
+
+    ManifestValidation {
+        extension: Some(extension),
+        diagnostics,
+    }
+}
+
+// The keyword the user configured for the extension, falling back to the manifest one
+pub fn get_extension_keyword(extension: &Extension, settings: &Settings) -> String {
+    settings
+        .extensions
+        .iter()
+        .find(|setting| setting.extension_id == extension.id && setting.setting_id == "keyword")
+        .map(|setting| setting.setting_value.to_string())
+        .unwrap_or(extension.keyword.to_owned())
+}
+
+pub fn find_keyword_owner(
+    keyword: &str,
+    settings: &Settings,
+    extensions: &[Extension],
+) -> Option<KeywordOwner> {
+    if keyword == settings.search_keyword {
+        return Some(KeywordOwner::SearchKeyword);
+    }
+
+    if let Some(engine) = settings
+        .search_engines
+        .iter()
+        .find(|engine| engine.keyword == keyword)
+    {
+        return Some(KeywordOwner::SearchEngine(engine.id));
+    }
+
+    extensions
+        .iter()
+        .find(|extension| get_extension_keyword(extension, settings) == keyword)
+        .map(|extension| KeywordOwner::Extension(extension.id.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::extensions::{get_extensions, try_index_extensions},
+        paths::get_extensions_dir,
+        settings::{get_default_settings, ExtensionSetting, SettingValue},
+        testing::set_test_home,
+    };
+
+    use super::*;
+
+    fn get_manifest(id: &str, keyword: &str, extra: &str) -> String {
+        format!(
+            r#"{{"id": "{}", "name": "Name", "description": "", "keyword": "{}"{}}}"#,
+            id, keyword, extra
+        )
+    }
+
+    fn get_kinds(json: &str, indexed: &[(PathBuf, Extension)]) -> Vec<DiagnosticKind> {
+        validate_manifest_str("manifest.json", json, indexed, &get_default_settings())
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.kind)
+            .collect()
+    }
+
+    fn get_indexed(id: &str, keyword: &str) -> Vec<(PathBuf, Extension)> {
+        vec![(
+            PathBuf::from(format!("{}/manifest.json", id)),
+            parse_manifest(&get_manifest(id, keyword, "")).unwrap(),
+        )]
+    }
+
+    #[test]
+    fn parse_errors_have_their_position() {
+        let json = "{\n  \"id\": \"a\",\n  \"name\": }";
+        let validation =
+            validate_manifest_str("a/manifest.json", json, &[], &get_default_settings());
+
+        assert!(validation.extension.is_none());
+        assert!(validation.diagnostics[0].is_error());
+        assert!(matches!(
+            validation.diagnostics[0].kind,
+            DiagnosticKind::ParseError {
+                line: 3,
+                column: 11,
+                ..
+            }
+        ));
+        assert!(validation.diagnostics[0]
+            .to_string()
+            .starts_with("a/manifest.json: "));
+    }
+
+    #[test]
+    fn valid_manifest_has_no_diagnostics() {
+        let validation = validate_manifest_str(
+            "manifest.json",
+            &get_manifest("notes", "n", ""),
+            &get_indexed("other", "o"),
+            &get_default_settings(),
+        );
+
+        assert_eq!(validation.extension.unwrap().id, "notes");
+        assert!(validation.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn duplicate_ids_are_errors() {
+        let kinds = get_kinds(&get_manifest("notes", "n2", ""), &get_indexed("notes", "n"));
+
+        assert!(matches!(
+            &kinds[..],
+            [DiagnosticKind::DuplicateId { extension_id, first_path }]
+                if extension_id == "notes" && first_path == Path::new("notes/manifest.json")
+        ));
+    }
+
+    #[test]
+    fn keyword_collisions_name_the_owner() {
+        let settings = get_default_settings();
+        let engine = &settings.search_engines[0];
+
+        let owners: Vec<KeywordOwner> = [
+            settings.search_keyword.to_owned(),
+            engine.keyword.to_owned(),
+            String::from("o"),
+        ]
+        .iter()
+        .flat_map(|keyword| {
+            get_kinds(
+                &get_manifest("notes", keyword, ""),
+                &get_indexed("other", "o"),
+            )
+        })
+        .filter_map(|kind| match kind {
+            DiagnosticKind::KeywordCollision { owner, .. } => Some(owner),
+            _ => None,
+        })
+        .collect();
+
+        assert_eq!(
+            owners,
+            [
+                KeywordOwner::SearchKeyword,
+                KeywordOwner::SearchEngine(engine.id),
+                KeywordOwner::Extension(String::from("other")),
+            ]
+        );
+    }
+
+    #[test]
+    fn configured_keyword_is_the_one_checked() {
+        let mut settings = get_default_settings();
+        settings.extensions.push(ExtensionSetting {
+            extension_id: String::from("notes"),
+            setting_id: String::from("keyword"),
+            setting_value: SettingValue::Text(String::from("o")),
+        });
+
+        let validation = validate_manifest_str(
+            "manifest.json",
+            &get_manifest("notes", "n", ""),
+            &get_indexed("other", "o"),
+            &settings,
+        );
+
+        assert!(matches!(
+            &validation.diagnostics[0].kind,
+            DiagnosticKind::KeywordCollision { keyword, .. } if keyword == "o"
+        ));
+        assert!(!validation.diagnostics[0].is_error());
+    }
+
+    #[test]
+    fn setting_problems_are_warnings() {
+        let settings = r#", "settings": [
+            {
+                "id": "mode",
+                "title": "Mode",
+                "description": "",
+                "setting_type": "Select",
+                "default_value": "fast",
+                "select_options": [{"id": "slow", "value": "Slow"}]
+            },
+            {
+                "id": "path",
+                "title": "Path",
+                "description": "",
+                "setting_type": "Path",
+                "default_value": "",
+                "show_conditions": [{"setting_id": "missing", "setting_value": "true"}]
+            }
+        ]"#;
+
+        let validation = validate_manifest_str(
+            "manifest.json",
+            &get_manifest("notes", "n", settings),
+            &[],
+            &get_default_settings(),
+        );
+
+        assert!(validation.extension.is_some());
+        assert!(validation
+            .diagnostics
+            .iter()
+            .all(|diagnostic| !diagnostic.is_error()));
+
+        assert!(matches!(
+            &validation.diagnostics[..],
+            [
+                ManifestDiagnostic {
+                    kind: DiagnosticKind::InvalidSelectDefault { setting_id, default_value, .. },
+                    ..
+                },
+                ManifestDiagnostic {
+                    kind: DiagnosticKind::UnknownShowCondition { condition_setting_id, .. },
+                    ..
+                },
+            ] if setting_id == "mode" && default_value == "fast" && condition_setting_id == "missing"
+        ));
+    }
+
+    #[test]
+    fn newer_api_versions_are_errors() {
+        let extra = format!(r#", "api_version": {}"#, PROTOCOL_VERSION + 1);
+        let validation = validate_manifest_str(
+            "manifest.json",
+            &get_manifest("notes", "n", &extra),
+            &[],
+            &get_default_settings(),
+        );
+
+        assert!(validation.diagnostics[0].is_error());
+        assert!(matches!(
+            validation.diagnostics[0].kind,
+            DiagnosticKind::UnsupportedApiVersion { api_version, .. }
+                if api_version == PROTOCOL_VERSION + 1
+        ));
+
+        let current = format!(r#", "api_version": {}"#, PROTOCOL_VERSION);
+        assert!(get_kinds(&get_manifest("notes", "n", &current), &[]).is_empty());
+    }
+
+    #[test]
+    fn indexing_reports_broken_manifests_and_keeps_the_rest() {
+        let _home = set_test_home("manifest-indexing");
+        let extensions_dir = get_extensions_dir();
+
+        let manifests = [
+            ("a-notes", get_manifest("notes", "n", "")),
+            ("b-broken", String::from("{\"id\": ")),
+            ("c-notes-copy", get_manifest("notes", "n2", "")),
+            (
+                "d-future",
+                get_manifest(
+                    "future",
+                    "f",
+                    &format!(r#", "api_version": {}"#, PROTOCOL_VERSION + 1),
+                ),
+            ),
+            ("e-clash", get_manifest("clash", "n", "")),
+        ];
+
+        for (dir, json) in manifests {
+            fs::create_dir_all(extensions_dir.join(dir)).unwrap();
+            fs::write(extensions_dir.join(dir).join("manifest.json"), json).unwrap();
+        }
+
+        let report = try_index_extensions().unwrap();
+
+        assert_eq!(report.indexed, ["notes", "clash"]);
+        assert!(report.has_errors());
+
+        let kinds: Vec<(&str, &str)> = report
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let dir = diagnostic
+                    .path
+                    .parent()
+                    .and_then(|dir| dir.file_name())
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default();
+
+                let kind = match diagnostic.kind {
+                    DiagnosticKind::ParseError { .. } => "parse",
+                    DiagnosticKind::DuplicateId { .. } => "duplicate",
+                    DiagnosticKind::UnsupportedApiVersion { .. } => "version",
+                    DiagnosticKind::KeywordCollision { .. } => "keyword",
+                    _ => "other",
+                };
+
+                (dir, kind)
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [
+                ("b-broken", "parse"),
+                ("c-notes-copy", "duplicate"),
+                ("d-future", "version"),
+                ("e-clash", "keyword"),
+            ]
+        );
+
+        let ids: Vec<String> = get_extensions()
+            .into_iter()
+            .map(|extension| extension.id)
+            .collect();
+        assert_eq!(ids, ["notes", "clash"]);
+    }
+}
@@ -1,3 +1,11 @@
 pub mod settings;
 pub mod apps;
-pub mod extensions;
\ No newline at end of file
+pub mod extensions;
+pub mod sessions;
+pub mod persistent;
+pub mod sdk;
+pub mod manifest;
+#[cfg(feature = "importers")]
+pub mod search_engines;
+pub mod shortcuts;
+pub mod store;
@@ -0,0 +1,344 @@
+use std::{
+    env,
+    io::{self, BufReader, BufWriter, Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::WhiskersError,
+    extension::Extension,
+    protocol::{negotiate_version, Versioned, WireFormat, PROTOCOL_VERSION, WIRE_FORMAT_ENV},
+};
+
+use super::extensions::{ExtensionRequest, ExtensionResponse};
+
+pub const PERSISTENT_MODE_ENV: &str = "WHISKERS_PERSISTENT_MODE";
+
+// Guards against allocating huge buffers when the other side writes garbage to the pipe
+const MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn is_persistent_mode() -> bool {
+    env::var(PERSISTENT_MODE_ENV).is_ok_and(|value| value == "1")
+}
+
+pub fn write_frame<T: Versioned>(writer: &mut impl Write, value: &T) -> Result<(), WhiskersError> {
+    write_frame_as(writer, value, WireFormat::Bincode)
+}
+
+pub fn read_frame<T: Versioned>(reader: &mut impl Read) -> Result<Option<T>, WhiskersError> {
+    read_frame_as(reader, WireFormat::Bincode)
+}
+
+pub fn write_frame_as<T: Versioned>(
+    writer: &mut impl Write,
+    value: &T,
+    format: WireFormat,
+) -> Result<(), WhiskersError> {
+    write_frame_version(writer, value, format, PROTOCOL_VERSION)
+}
+
+// A frame is the payload length as a little endian u32 followed by the encoded payload
+pub fn write_frame_version<T: Versioned>(
+    writer: &mut impl Write,
+    value: &T,
+    format: WireFormat,
+    version: u16,
+) -> Result<(), WhiskersError> {
+    let payload = format.encode_version(value, version)?;
+
+    if payload.len() > MAX_FRAME_LENGTH {
+        return Err(WhiskersError::Encode(format!(
+            "Frame of {} bytes exceeds the maximum length",
+            payload.len()
+        )));
+    }
+
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Returns None when the other side closed the pipe before a new frame started
+pub fn read_frame_as<T: Versioned>(
+    reader: &mut impl Read,
+    format: WireFormat,
+) -> Result<Option<T>, WhiskersError> {
+    match read_payload(reader)? {
+        Some(payload) => format.decode(&payload).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn read_payload(reader: &mut impl Read) -> Result<Option<Vec<u8>>, WhiskersError> {
+    let mut length_bytes = [0u8; 4];
+    let mut read = 0;
+
+    // A pipe closed in the middle of the length is a truncated frame, not a clean end
+    while read < length_bytes.len() {
+        match reader.read(&mut length_bytes[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(WhiskersError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Frame length ended early",
+                )))
+            }
+            Ok(count) => read += count,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    let length = u32::from_le_bytes(length_bytes) as usize;
+
+    if length > MAX_FRAME_LENGTH {
+        return Err(WhiskersError::Decode(format!(
+            "Frame of {} bytes exceeds the maximum length",
+            length
+        )));
+    }
+
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)?;
+
+    Ok(Some(payload))
+}
+
+// Serves requests from the launcher until it closes stdin. Persistent extensions must not print
+// anything else to stdout since it's used for the responses. Each response is written in the
+// version of its request so an older launcher can read it.
+pub fn try_run_extension(
+    mut handler: impl FnMut(ExtensionRequest) -> ExtensionResponse,
+) -> Result<(), WhiskersError> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut writer = BufWriter::new(stdout.lock());
+    let format = WireFormat::from_env();
+
+    while let Some(payload) = read_payload(&mut reader)? {
+        let request: ExtensionRequest = format.decode(&payload)?;
+        let response = handler(request);
+        write_frame_version(&mut writer, &response, format, format.get_version(&payload))?;
+    }
+
+    Ok(())
+}
+
+pub fn run_extension(handler: impl FnMut(ExtensionRequest) -> ExtensionResponse) {
+    try_run_extension(handler).expect("Error running persistent extension");
+}
+
+// The output is read on its own thread so that a request can give up on an extension that hangs
+pub struct ExtensionProcess {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    payloads: Receiver<Result<Option<Vec<u8>>, WhiskersError>>,
+    format: WireFormat,
+    version: u16,
+    timeout: Duration,
+}
+
+impl ExtensionProcess {
+    pub fn try_spawn(command: Command) -> Result<Self, WhiskersError> {
+        Self::try_spawn_with_format(command, WireFormat::Bincode, PROTOCOL_VERSION)
+    }
+
+    pub fn spawn(command: Command) -> Self {
+        Self::try_spawn(command).expect("Error spawning extension process")
+    }
+
+    // Talks to the extension in the format and protocol version it understands
+    pub fn try_spawn_for(command: Command, extension: &Extension) -> Result<Self, WhiskersError> {
+        let version = negotiate_version(extension.api_version)?;
+        Self::try_spawn_with_format(command, extension.protocol, version)
+    }
+
+    pub fn spawn_for(command: Command, extension: &Extension) -> Self {
+        Self::try_spawn_for(command, extension).expect("Error spawning extension process")
+    }
+
+    fn try_spawn_with_format(
+        mut command: Command,
+        format: WireFormat,
+        version: u16,
+    ) -> Result<Self, WhiskersError> {
+        let mut child = command
+            .env(PERSISTENT_MODE_ENV, "1")
+            .env(WIRE_FORMAT_ENV, format.name())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| {
+            WhiskersError::Io(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Error opening extension stdin",
+            ))
+        })?;
+
+        let stdout = child.stdout.take().ok_or_else(|| {
+            WhiskersError::Io(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Error opening extension stdout",
+            ))
+        })?;
+
+        let (sender, payloads) = mpsc::channel();
+
+        // Stops once the output closes or a frame can't be read, which ends every later request
+        thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+
+            loop {
+                let payload = read_payload(&mut stdout);
+                let is_last = !matches!(payload, Ok(Some(_)));
+
+                if sender.send(payload).is_err() || is_last {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin: BufWriter::new(stdin),
+            payloads,
+            format,
+            version,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        })
+    }
+
+    // How long a request waits for the response before the extension is killed
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn try_request(
+        &mut self,
+        request: &ExtensionRequest,
+    ) -> Result<ExtensionResponse, WhiskersError> {
+        write_frame_version(&mut self.stdin, request, self.format, self.version)?;
+
+        let closed_error = || {
+            WhiskersError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Extension process closed its output",
+            ))
+        };
+
+        match self.payloads.recv_timeout(self.timeout) {
+            Ok(Ok(Some(payload))) => self.format.decode(&payload),
+            Ok(Ok(None)) | Err(RecvTimeoutError::Disconnected) => Err(closed_error()),
+            Ok(Err(error)) => Err(error),
+            Err(RecvTimeoutError::Timeout) => {
+                // A late response would answer the next request so the extension can't be kept
+                let _ = self.child.kill();
+
+                Err(WhiskersError::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Extension process didn't respond within {} ms",
+                        self.timeout.as_millis()
+                    ),
+                )))
+            }
+        }
+    }
+
+    pub fn request(&mut self, request: &ExtensionRequest) -> ExtensionResponse {
+        self.try_request(request)
+            .expect("Error requesting extension response")
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+}
+
+impl Drop for ExtensionProcess {
+    fn drop(&mut self) {
+        if self.is_running() {
+            let _ = self.child.kill();
+        }
+
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use std::time::Instant;
+
+    use crate::api::extensions::ActionContext;
+
+    use super::*;
+
+    fn get_request() -> ExtensionRequest {
+        ExtensionRequest::new("test", ActionContext::ResultsRequest)
+    }
+
+    #[test]
+    fn request_reads_the_response_frame() {
+        let json = r#"{"results":[],"args":["ok"]}"#;
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "printf '\\{:03o}\\000\\000\\000%s' '{}'; sleep 1",
+            json.len(),
+            json
+        ));
+
+        let mut process =
+            ExtensionProcess::try_spawn_with_format(command, WireFormat::Json, PROTOCOL_VERSION)
+                .unwrap();
+        let response = process.try_request(&get_request()).unwrap();
+
+        assert_eq!(response.args, Some(vec![String::from("ok")]));
+    }
+
+    #[test]
+    fn request_times_out_and_kills_the_extension() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 10");
+
+        let mut process = ExtensionProcess::try_spawn(command).unwrap();
+        process.set_timeout(Duration::from_millis(100));
+
+        let started = Instant::now();
+        let error = process.try_request(&get_request()).unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            error,
+            WhiskersError::Io(error) if error.kind() == io::ErrorKind::TimedOut
+        ));
+
+        let _ = process.child.wait();
+        assert!(!process.is_running());
+    }
+
+    #[test]
+    fn request_fails_when_the_output_closes() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("exec >&-; sleep 1");
+
+        let mut process = ExtensionProcess::try_spawn(command).unwrap();
+
+        assert!(matches!(
+            process.try_request(&get_request()),
+            Err(WhiskersError::Io(error)) if error.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+}
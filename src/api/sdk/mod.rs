@@ -0,0 +1,67 @@
+use std::process::exit;
+
+use crate::result::WLResult;
+
+use super::{
+    extensions::{
+        get_extension_request, try_get_dialog_response, write_extension_response, ActionContext,
+        DialogResponse, ExtensionRequest, ExtensionResponse,
+    },
+    persistent::{is_persistent_mode, run_extension},
+};
+
+pub trait WhiskersExtension {
+    fn results(&self, search: &str, request: &ExtensionRequest) -> Vec<WLResult>;
+
+    fn run_action(&self, _action: &str, _args: Option<Vec<String>>) {}
+
+    fn on_dialog(&self, _response: DialogResponse) {}
+}
+
+// Entry point for extensions. Reads the launcher request, calls the matching handler and sends
+// the response back using either the request files or the persistent stdio mode.
+pub fn whiskers_main<E: WhiskersExtension + Default>() {
+    let extension = E::default();
+
+    if is_persistent_mode() {
+        run_extension(|request| handle_request(&extension, request));
+        return;
+    }
+
+    let request = get_extension_request();
+    let is_results_request = matches!(request.action_context, ActionContext::ResultsRequest);
+    let response = handle_request(&extension, request);
+
+    if is_results_request {
+        write_extension_response(response);
+    }
+
+    exit(0);
+}
+
+pub fn handle_request(
+    extension: &impl WhiskersExtension,
+    request: ExtensionRequest,
+) -> ExtensionResponse {
+    match request.action_context {
+        ActionContext::ResultsRequest => {
+            let search_text = request.search_text.to_owned().unwrap_or_default();
+            ExtensionResponse::new(extension.results(&search_text, &request))
+        }
+        ActionContext::RunAction => {
+            if let Some(action) = &request.extension_action {
+                extension.run_action(action, request.args.to_owned());
+            }
+
+            ExtensionResponse::new(vec![])
+        }
+        // A missing or unreadable response is skipped so a persistent extension keeps running
+        ActionContext::DialogResponse => {
+            if let Ok(response) = try_get_dialog_response() {
+                extension.on_dialog(response);
+            }
+
+            ExtensionResponse::new(vec![])
+        }
+    }
+}
@@ -0,0 +1,668 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+
+use crate::{
+    api::settings::try_update_settings,
+    error::{decode_error, WhiskersError},
+    settings::{SearchEngine, Settings},
+    storage::get_temp_path,
+    utils::encode_uri_component,
+};
+
+const MOZLZ4_MAGIC: &[u8] = b"mozLz40\0";
+
+#[derive(Debug, Clone)]
+pub enum SearchEngineSource {
+    OpenSearch(PathBuf),
+    Firefox(PathBuf),
+    Chromium(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedSearchEngine {
+    pub name: String,
+    pub keyword: Option<String>,
+    pub search_query: String,
+    pub suggestions_query: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FirefoxSearch {
+    #[serde(default)]
+    engines: Vec<FirefoxEngine>,
+}
+
+#[derive(Deserialize)]
+struct FirefoxEngine {
+    #[serde(rename = "_name")]
+    name: String,
+    #[serde(rename = "_urls", default)]
+    urls: Vec<FirefoxUrl>,
+    #[serde(rename = "_definedAliases", default)]
+    defined_aliases: Vec<String>,
+    #[serde(rename = "_metaData", default)]
+    metadata: FirefoxMetadata,
+}
+
+#[derive(Deserialize)]
+struct FirefoxUrl {
+    template: String,
+    #[serde(rename = "type")]
+    url_type: Option<String>,
+    #[serde(default)]
+    params: Vec<FirefoxParam>,
+}
+
+// Params that depend on how the search was started only have a condition and no value
+#[derive(Deserialize)]
+struct FirefoxParam {
+    name: String,
+    #[serde(default)]
+    value: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct FirefoxMetadata {
+    alias: Option<String>,
+}
+
+impl ImportedSearchEngine {
+    pub fn new(name: impl Into<String>, search_query: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            keyword: None,
+            search_query: search_query.into(),
+            suggestions_query: None,
+        }
+    }
+
+    pub fn keyword(&mut self, keyword: impl Into<String>) -> Self {
+        self.keyword = Some(keyword.into());
+        self.to_owned()
+    }
+
+    pub fn suggestions_query(&mut self, suggestions_query: impl Into<String>) -> Self {
+        self.suggestions_query = Some(suggestions_query.into());
+        self.to_owned()
+    }
+}
+
+pub fn try_read_search_engines(
+    source: &SearchEngineSource,
+) -> Result<Vec<ImportedSearchEngine>, WhiskersError> {
+    match source {
+        SearchEngineSource::OpenSearch(path) => {
+            Ok(vec![parse_opensearch(&fs::read_to_string(path)?)?])
+        }
+        SearchEngineSource::Firefox(profile_dir) => read_firefox_search_engines(profile_dir),
+        SearchEngineSource::Chromium(profile_dir) => read_chromium_search_engines(profile_dir),
+    }
+}
+
+// Reads the engines of the source and adds the ones the user doesn't have yet to the settings
+pub fn try_import_search_engines(
+    source: &SearchEngineSource,
+) -> Result<Vec<SearchEngine>, WhiskersError> {
+    let imported = try_read_search_engines(source)?;
+    let mut added = Vec::<SearchEngine>::new();
+
+    try_update_settings(|settings| {
+        added = merge_search_engines(settings, imported);
+    })?;
+
+    Ok(added)
+}
+
+pub fn import_search_engines(source: &SearchEngineSource) -> Vec<SearchEngine> {
+    try_import_search_engines(source).expect("Error importing search engines")
+}
+
+// Engines with a search query the user already has are skipped. New engines get the next free
+// ids and a keyword nothing else uses, adding a number to it when it's taken.
+pub fn merge_search_engines(
+    settings: &mut Settings,
+    imported: Vec<ImportedSearchEngine>,
+) -> Vec<SearchEngine> {
+    let mut added = Vec::<SearchEngine>::new();
+
+    for engine in imported {
+        let is_known = settings
+            .search_engines
+            .iter()
+            .any(|known| known.search_query == engine.search_query);
+
+        if is_known {
+            continue;
+        }
+
+        let id = settings
+            .search_engines
+            .iter()
+            .map(|known| known.id + 1)
+            .max()
+            .unwrap_or(0);
+
+        let base_keyword = engine
+            .keyword
+            .as_deref()
+            .map(normalize_keyword)
+            .filter(|keyword| !keyword.is_empty())
+            .unwrap_or(get_keyword_from_name(&engine.name));
+
+        let mut keyword = base_keyword.to_owned();
+        let mut suffix = 2;
+
+        while is_keyword_taken(&keyword, settings) {
+            keyword = format!("{}{}", base_keyword, suffix);
+            suffix += 1;
+        }
+
+        let search_engine = SearchEngine {
+            id,
+            icon_path: None,
+            tint_icon: false,
+            keyword,
+            name: engine.name,
+            search_query: engine.search_query,
+            suggestions_query: engine.suggestions_query,
+        };
+
+        settings.search_engines.push(search_engine.to_owned());
+        added.push(search_engine);
+    }
+
+    added
+}
+
+fn is_keyword_taken(keyword: &str, settings: &Settings) -> bool {
+    keyword == settings.search_keyword
+        || settings
+            .search_engines
+            .iter()
+            .any(|engine| engine.keyword == keyword)
+        || settings.extensions.iter().any(|setting| {
+            setting.setting_id == "keyword" && setting.setting_value.to_string() == keyword
+        })
+}
+
+fn normalize_keyword(keyword: &str) -> String {
+    keyword
+        .trim()
+        .trim_start_matches('@')
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("")
+}
+
+// The lowercase first word of the name, e.g. "wikipedia" for "Wikipedia (en)"
+fn get_keyword_from_name(name: &str) -> String {
+    let keyword: String = name
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|char| char.is_alphanumeric())
+        .flat_map(|char| char.to_lowercase())
+        .collect();
+
+    if keyword.is_empty() {
+        String::from("search")
+    } else {
+        keyword
+    }
+}
+
+// Parses an OpenSearch description document. The text/html url is the search query and the
+// application/x-suggestions+json url, when there is one, the suggestions query.
+pub fn parse_opensearch(xml: &str) -> Result<ImportedSearchEngine, WhiskersError> {
+    let document = roxmltree::Document::parse(xml).map_err(decode_error)?;
+    let root = document.root_element();
+
+    if root.tag_name().name() != "OpenSearchDescription" {
+        return Err(WhiskersError::Decode(String::from(
+            "Not an OpenSearch description",
+        )));
+    }
+
+    let get_text = |name: &str| {
+        root.children()
+            .find(|node| node.tag_name().name() == name)
+            .and_then(|node| node.text())
+            .map(|text| text.trim().to_owned())
+            .filter(|text| !text.is_empty())
+    };
+
+    let name = get_text("ShortName").ok_or(WhiskersError::Decode(String::from(
+        "OpenSearch description has no ShortName",
+    )))?;
+
+    let get_url = |url_type: &str| {
+        root.children()
+            .filter(|node| node.tag_name().name() == "Url")
+            .filter(|node| {
+                node.attribute("method")
+                    .is_none_or(|method| method.eq_ignore_ascii_case("get"))
+            })
+            .find(|node| node.attribute("type") == Some(url_type))
+            .and_then(|node| {
+                let params: Vec<(String, String)> = node
+                    .children()
+                    .filter(|child| child.tag_name().name() == "Param")
+                    .filter_map(|child| {
+                        Some((
+                            child.attribute("name")?.to_owned(),
+                            child.attribute("value")?.to_owned(),
+                        ))
+                    })
+                    .collect();
+
+                node.attribute("template")
+                    .map(|template| get_search_query(template, &params))
+            })
+    };
+
+    let search_query = get_url("text/html").ok_or(WhiskersError::Decode(String::from(
+        "OpenSearch description has no text/html url",
+    )))?;
+
+    let mut engine = ImportedSearchEngine::new(name, search_query);
+
+    if let Some(suggestions_query) = get_url("application/x-suggestions+json") {
+        engine.suggestions_query(suggestions_query);
+    }
+
+    Ok(engine)
+}
+
+// Turns an OpenSearch template into a search query, appending the url params and dropping the
+// optional parameters the launcher doesn't fill in
+fn get_search_query(template: &str, params: &[(String, String)]) -> String {
+    let mut query = template.to_owned();
+
+    for (name, value) in params {
+        query.push(if query.contains('?') { '&' } else { '?' });
+        query.push_str(&format!("{}={}", encode_param(name), encode_param(value)));
+    }
+
+    query = query.replace("{searchTerms}", "%s");
+
+    let mut search_query = String::with_capacity(query.len());
+    let mut rest = query.as_str();
+
+    while let Some(start) = rest.find('{') {
+        search_query.push_str(&rest[..start]);
+
+        match rest[start..].find('}') {
+            Some(end) => {
+                let placeholder = &rest[start..start + end + 1];
+
+                if matches!(placeholder, "{lang}" | "{language}") {
+                    search_query.push_str(placeholder);
+                }
+
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                search_query.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    search_query.push_str(rest);
+    search_query
+}
+
+// Percent encodes a url param, leaving the placeholders in it as they are
+fn encode_param(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        match rest[start..].find('}') {
+            Some(end) => {
+                encoded.push_str(&encode_uri_component(&rest[..start]));
+                encoded.push_str(&rest[start..start + end + 1]);
+                rest = &rest[start + end + 1..];
+            }
+            None => break,
+        }
+    }
+
+    encoded.push_str(&encode_uri_component(rest));
+    encoded
+}
+
+// Decompresses a Firefox mozlz4 file, the magic followed by an lz4 block with its size prepended
+pub fn decompress_mozlz4(bytes: &[u8]) -> Result<Vec<u8>, WhiskersError> {
+    let block = bytes
+        .strip_prefix(MOZLZ4_MAGIC)
+        .ok_or(WhiskersError::Decode(String::from("Not a mozlz4 file")))?;
+
+    lz4_flex::block::decompress_size_prepended(block).map_err(decode_error)
+}
+
+// Engines that ship with Firefox don't store their urls in the profile so only the ones the user
+// added can be imported
+pub fn read_firefox_search_engines(
+    profile_dir: impl AsRef<Path>,
+) -> Result<Vec<ImportedSearchEngine>, WhiskersError> {
+    let bytes = fs::read(profile_dir.as_ref().join("search.json.mozlz4"))?;
+    let json = decompress_mozlz4(&bytes)?;
+    let search: FirefoxSearch = serde_json::from_slice(&json).map_err(decode_error)?;
+
+    let engines = search
+        .engines
+        .into_iter()
+        .filter_map(|engine| {
+            let get_url = |url_type: &str| {
+                engine
+                    .urls
+                    .iter()
+                    .find(|url| url.url_type.as_deref().unwrap_or("text/html") == url_type)
+                    .map(|url| {
+                        let params: Vec<(String, String)> = url
+                            .params
+                            .iter()
+                            .filter_map(|param| {
+                                Some((param.name.to_owned(), param.value.to_owned()?))
+                            })
+                            .collect();
+
+                        get_search_query(&url.template, &params)
+                    })
+            };
+
+            let mut imported = ImportedSearchEngine::new(&engine.name, get_url("text/html")?);
+
+            if let Some(suggestions_query) = get_url("application/x-suggestions+json") {
+                imported.suggestions_query(suggestions_query);
+            }
+
+            let alias = engine
+                .metadata
+                .alias
+                .to_owned()
+                .or(engine.defined_aliases.first().cloned())
+                .filter(|alias| !alias.is_empty());
+
+            if let Some(alias) = alias {
+                imported.keyword(alias);
+            }
+
+            Some(imported)
+        })
+        .collect();
+
+    Ok(engines)
+}
+
+// The Web Data database is locked while the browser runs so it's read from a copy
+pub fn read_chromium_search_engines(
+    profile_dir: impl AsRef<Path>,
+) -> Result<Vec<ImportedSearchEngine>, WhiskersError> {
+    let web_data_path = profile_dir.as_ref().join("Web Data");
+
+    let copy_path = get_temp_path(env::temp_dir().join("whiskers-web-data.sqlite"));
+    fs::copy(&web_data_path, &copy_path)?;
+
+    let result = read_chromium_keywords(&copy_path);
+    let _ = fs::remove_file(&copy_path);
+
+    result
+}
+
+fn read_chromium_keywords(path: &Path) -> Result<Vec<ImportedSearchEngine>, WhiskersError> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(decode_error)?;
+
+    let mut statement = connection
+        .prepare("SELECT short_name, keyword, url, suggest_url FROM keywords ORDER BY id")
+        .map_err(decode_error)?;
+
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(decode_error)?;
+
+    let mut engines = Vec::<ImportedSearchEngine>::new();
+
+    for row in rows {
+        let (name, keyword, url, suggest_url) = row.map_err(decode_error)?;
+
+        if !url.contains("{searchTerms}") {
+            continue;
+        }
+
+        let mut engine =
+            ImportedSearchEngine::new(name, get_search_query(&get_chromium_url(&url), &[]));
+
+        if !keyword.is_empty() {
+            engine.keyword(keyword);
+        }
+
+        if let Some(suggest_url) = suggest_url.filter(|url| url.contains("{searchTerms}")) {
+            engine.suggestions_query(get_search_query(&get_chromium_url(&suggest_url), &[]));
+        }
+
+        engines.push(engine);
+    }
+
+    Ok(engines)
+}
+
+// Chromium urls can start with its own base url placeholder
+fn get_chromium_url(url: &str) -> String {
+    url.replace("{google:baseURL}", "https://www.google.com/")
+        .replace(
+            "{google:baseSuggestURL}",
+            "https://www.google.com/complete/",
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        settings::{get_default_settings, ExtensionSetting, SettingValue},
+        testing::set_test_home,
+    };
+
+    use super::*;
+
+    const OPENSEARCH: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName> Example </ShortName>
+  <Url type="text/html" method="post" template="https://example.com/post"/>
+  <Url type="text/html" template="https://example.com/search">
+    <Param name="q" value="{searchTerms}"/>
+    <Param name="from" value="a b&amp;c"/>
+    <Param name="page" value="{startPage?}"/>
+    <Param name="novalue"/>
+  </Url>
+  <Url type="application/x-suggestions+json" template="https://example.com/suggest?q={searchTerms}&amp;hl={language}"/>
+</OpenSearchDescription>"#;
+
+    fn get_search_engine(id: usize, keyword: &str, search_query: &str) -> SearchEngine {
+        SearchEngine {
+            id,
+            icon_path: None,
+            tint_icon: false,
+            keyword: keyword.to_owned(),
+            name: keyword.to_owned(),
+            search_query: search_query.to_owned(),
+            suggestions_query: None,
+        }
+    }
+
+    #[test]
+    fn opensearch_urls_are_read_with_encoded_params() {
+        let engine = parse_opensearch(OPENSEARCH).unwrap();
+
+        assert_eq!(engine.name, "Example");
+        assert_eq!(engine.keyword, None);
+        assert_eq!(
+            engine.search_query,
+            "https://example.com/search?q=%s&from=a%20b%26c&page="
+        );
+        assert_eq!(
+            engine.suggestions_query.as_deref(),
+            Some("https://example.com/suggest?q=%s&hl={language}")
+        );
+    }
+
+    #[test]
+    fn invalid_opensearch_is_rejected() {
+        let invalid = [
+            "<Description><ShortName>A</ShortName></Description>",
+            "<OpenSearchDescription><Url type=\"text/html\" template=\"https://a\"/></OpenSearchDescription>",
+            "<OpenSearchDescription><ShortName>A</ShortName></OpenSearchDescription>",
+            "<OpenSearchDescription>",
+        ];
+
+        for xml in invalid {
+            assert!(
+                matches!(parse_opensearch(xml), Err(WhiskersError::Decode(_))),
+                "{}",
+                xml
+            );
+        }
+    }
+
+    #[test]
+    fn firefox_engines_are_read_from_mozlz4() {
+        let home = set_test_home("search-engines-firefox");
+
+        let json = r#"{
+            "engines": [
+                {
+                    "_name": "Example",
+                    "_metaData": {"alias": "ex"},
+                    "_urls": [
+                        {
+                            "template": "https://example.com/search",
+                            "params": [
+                                {"name": "q", "value": "{searchTerms}"},
+                                {"name": "channel", "condition": "purpose", "purpose": "contextmenu"},
+                                {"name": "lang name", "value": "en/us"}
+                            ]
+                        },
+                        {
+                            "template": "https://example.com/suggest?q={searchTerms}",
+                            "type": "application/x-suggestions+json"
+                        }
+                    ]
+                },
+                {
+                    "_name": "Other",
+                    "_definedAliases": ["@other"],
+                    "_urls": [{"template": "https://other.com/?q={searchTerms}", "type": "text/html"}]
+                },
+                {"_name": "Built In"}
+            ]
+        }"#;
+
+        let mut bytes = MOZLZ4_MAGIC.to_vec();
+        bytes.extend(lz4_flex::block::compress_prepend_size(json.as_bytes()));
+        fs::write(home.path.join("search.json.mozlz4"), bytes).unwrap();
+
+        let engines =
+            try_read_search_engines(&SearchEngineSource::Firefox(home.path.to_owned())).unwrap();
+
+        assert_eq!(
+            engines,
+            vec![
+                ImportedSearchEngine::new(
+                    "Example",
+                    "https://example.com/search?q=%s&lang%20name=en%2Fus"
+                )
+                .keyword("ex")
+                .suggestions_query("https://example.com/suggest?q=%s"),
+                ImportedSearchEngine::new("Other", "https://other.com/?q=%s").keyword("@other"),
+            ]
+        );
+
+        assert!(matches!(
+            decompress_mozlz4(b"notmozlz"),
+            Err(WhiskersError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn chromium_engines_are_read_from_web_data() {
+        let home = set_test_home("search-engines-chromium");
+
+        let connection = Connection::open(home.path.join("Web Data")).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE keywords (id INTEGER PRIMARY KEY, short_name TEXT, keyword TEXT, \
+                 url TEXT, suggest_url TEXT);
+                 INSERT INTO keywords VALUES (1, 'Google', 'google.com', \
+                 '{google:baseURL}search?q={searchTerms}&{google:RLZ}', \
+                 '{google:baseSuggestURL}search?q={searchTerms}');
+                 INSERT INTO keywords VALUES (2, 'History', 'history', 'chrome://history', NULL);
+                 INSERT INTO keywords VALUES (3, 'Wiki', '', \
+                 'https://wiki.org/?search={searchTerms}', '');",
+            )
+            .unwrap();
+        drop(connection);
+
+        let engines =
+            try_read_search_engines(&SearchEngineSource::Chromium(home.path.to_owned())).unwrap();
+
+        assert_eq!(
+            engines,
+            vec![
+                ImportedSearchEngine::new("Google", "https://www.google.com/search?q=%s&")
+                    .keyword("google.com")
+                    .suggestions_query("https://www.google.com/complete/search?q=%s"),
+                ImportedSearchEngine::new("Wiki", "https://wiki.org/?search=%s"),
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_keywords_get_a_free_suffix() {
+        let mut settings = get_default_settings();
+        settings.search_keyword = String::from("s");
+        settings.search_engines = vec![get_search_engine(4, "wiki", "https://wiki.org/?q=%s")];
+        settings.extensions = vec![ExtensionSetting {
+            extension_id: String::from("extension"),
+            setting_id: String::from("keyword"),
+            setting_value: SettingValue::Text(String::from("wiki2")),
+        }];
+
+        let added = merge_search_engines(
+            &mut settings,
+            vec![
+                ImportedSearchEngine::new("Wiki", "https://wiki.org/?q=%s").keyword("other"),
+                ImportedSearchEngine::new("Wikipedia", "https://en.wiki.org/?q=%s")
+                    .keyword("@wiki"),
+                ImportedSearchEngine::new("Wiki (de)", "https://de.wiki.org/?q=%s"),
+                ImportedSearchEngine::new("Search", "https://search.com/?q=%s").keyword(" s "),
+                ImportedSearchEngine::new("!!!", "https://symbols.com/?q=%s"),
+            ],
+        );
+
+        let keywords: Vec<(usize, &str)> = added
+            .iter()
+            .map(|engine| (engine.id, engine.keyword.as_str()))
+            .collect();
+
+        assert_eq!(
+            keywords,
+            [(5, "wiki3"), (6, "wiki4"), (7, "s2"), (8, "search")]
+        );
+        assert_eq!(settings.search_engines.len(), 5);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    env, fs,
+    process::{self, Command},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::WhiskersError,
+    paths::{get_session_dir, get_sessions_dir},
+};
+
+pub const SESSION_ID_ENV: &str = "WHISKERS_SESSION_ID";
+pub const SESSION_ID_ARG: &str = "--session-id";
+
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub fn new_session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+
+    let counter = SESSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{:x}-{:x}-{:x}", process::id(), nanos, counter)
+}
+
+// The launcher passes the session either through the environment or as `--session-id <id>`
+pub fn get_session_id() -> Option<String> {
+    if let Ok(session_id) = env::var(SESSION_ID_ENV) {
+        if is_valid_session_id(&session_id) {
+            return Some(session_id);
+        }
+    }
+
+    let mut args = env::args();
+
+    while let Some(arg) = args.next() {
+        if arg == SESSION_ID_ARG {
+            return args
+                .next()
+                .filter(|session_id| is_valid_session_id(session_id));
+        }
+
+        if let Some(session_id) = arg.strip_prefix(&format!("{}=", SESSION_ID_ARG)) {
+            if is_valid_session_id(session_id) {
+                return Some(session_id.to_owned());
+            }
+        }
+    }
+
+    None
+}
+
+pub fn apply_session(command: &mut Command, session_id: impl Into<String>) -> &mut Command {
+    command.env(SESSION_ID_ENV, session_id.into())
+}
+
+pub fn is_valid_session_id(session_id: &str) -> bool {
+    !session_id.is_empty()
+        && session_id
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+}
+
+// Session ids become directory names so anything that could leave the sessions dir is refused
+pub fn check_session_id(session_id: &str) -> Result<(), WhiskersError> {
+    if is_valid_session_id(session_id) {
+        Ok(())
+    } else {
+        Err(WhiskersError::InvalidSession(session_id.to_owned()))
+    }
+}
+
+pub fn try_create_session(session_id: impl Into<String>) -> Result<(), WhiskersError> {
+    let session_id = session_id.into();
+    check_session_id(&session_id)?;
+
+    fs::create_dir_all(get_session_dir(session_id))?;
+    Ok(())
+}
+
+pub fn create_session(session_id: impl Into<String>) {
+    try_create_session(session_id).expect("Error creating session");
+}
+
+pub fn try_end_session(session_id: impl Into<String>) -> Result<(), WhiskersError> {
+    let session_id = session_id.into();
+    check_session_id(&session_id)?;
+
+    let session_dir = get_session_dir(session_id);
+
+    if session_dir.exists() {
+        fs::remove_dir_all(session_dir)?;
+    }
+
+    Ok(())
+}
+
+pub fn end_session(session_id: impl Into<String>) {
+    try_end_session(session_id).expect("Error ending session");
+}
+
+pub fn try_cleanup_stale_sessions(max_age: Duration) -> Result<usize, WhiskersError> {
+    let sessions_dir = get_sessions_dir();
+    let mut removed = 0;
+
+    if !sessions_dir.exists() {
+        return Ok(0);
+    }
+
+    for entry in fs::read_dir(&sessions_dir)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let modified = entry.metadata()?.modified()?;
+
+        let is_stale = match SystemTime::now().duration_since(modified) {
+            Ok(age) => age > max_age,
+            Err(_) => false,
+        };
+
+        if is_stale {
+            fs::remove_dir_all(entry.path())?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+pub fn cleanup_stale_sessions(max_age: Duration) -> usize {
+    try_cleanup_stale_sessions(max_age).expect("Error cleaning up stale sessions")
+}
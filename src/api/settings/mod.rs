@@ -1,47 +1,102 @@
-use std::fs;
+use std::{fs, io};
 
 #[cfg(target_os = "linux")]
 use std::os::unix::fs::PermissionsExt;
 
 use crate::{
+    error::WhiskersError,
     paths::{get_autostart_dir, get_settings_path},
+    protocol::{decode, encode},
     settings::{get_default_settings, Settings},
+    storage::{lock_exclusive, lock_shared, write_atomic},
 };
 
 #[cfg(target_os = "windows")]
-use {
-    crate::paths::{get_app_dir, get_app_resources_dir},
-    mslnk::ShellLink,
-    std::{env, path::Path}
-};
+use {crate::paths::get_app_dir, mslnk::ShellLink};
 
-pub fn get_settings() -> Settings {
+// Readers share the lock, only writing the defaults for a missing file needs it alone
+pub fn try_get_settings() -> Result<Settings, WhiskersError> {
     let settings_path = get_settings_path();
 
-    if !settings_path.parent().unwrap().exists() {
-        fs::create_dir_all(&settings_path.parent().unwrap())
-            .expect("Error creating settings directory");
-    }
+    if settings_path.exists() {
+        let _lock = lock_shared(&settings_path)?;
 
-    if !settings_path.exists() {
-        fs::write(
-            &settings_path,
-            bincode::serialize(&get_default_settings()).expect("Error serializing settings"),
-        )
-        .expect("Error writing settings");
+        match fs::read(&settings_path) {
+            Ok(bytes) => return decode(&bytes),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
     }
 
-    let settings_bytes = fs::read(get_settings_path()).expect("Error reading settings");
-    let decoded_settings = bincode::deserialize::<Settings>(&settings_bytes);
+    let _lock = lock_exclusive(&settings_path)?;
+    read_settings()
+}
 
-    match decoded_settings {
+// A settings file written by a newer launcher can't be read so the defaults are used until the
+// crate is updated. Writing still fails in that case, the newer file is never overwritten.
+//
+// Extensions built against 2.0 don't know about the protocol header and also fall back to the
+// defaults once the launcher saves the settings, they need to be rebuilt against this version to
+// see the user's settings again.
+pub fn get_settings() -> Settings {
+    match try_get_settings() {
         Ok(settings) => settings,
-        Err(_) => get_default_settings(),
+        Err(error) if is_unreadable_settings(&error) => get_default_settings(),
+        Err(error) => panic!("Error getting settings: {}", error),
     }
 }
 
+// Errors that mean the settings file exists but this version can't make sense of it
+pub(crate) fn is_unreadable_settings(error: &WhiskersError) -> bool {
+    matches!(
+        error,
+        WhiskersError::Decode(_) | WhiskersError::UnsupportedVersion { .. }
+    )
+}
+
+pub fn try_write_settings(settings: Settings) -> Result<(), WhiskersError> {
+    let _lock = lock_exclusive(get_settings_path())?;
+    store_settings(settings)
+}
+
 pub fn write_settings(settings: Settings) {
-    let current_settings = get_settings();
+    try_write_settings(settings).expect("Error writing settings");
+}
+
+// Reads, modifies and writes the settings while holding the settings lock so that concurrent
+// writers can't overwrite each other's changes
+pub fn try_update_settings(update: impl FnOnce(&mut Settings)) -> Result<Settings, WhiskersError> {
+    let _lock = lock_exclusive(get_settings_path())?;
+
+    let mut settings = read_settings()?;
+    update(&mut settings);
+    store_settings(settings.to_owned())?;
+
+    Ok(settings)
+}
+
+pub fn update_settings(update: impl FnOnce(&mut Settings)) -> Settings {
+    try_update_settings(update).expect("Error updating settings")
+}
+
+fn read_settings() -> Result<Settings, WhiskersError> {
+    let settings_path = get_settings_path();
+
+    if !settings_path.exists() {
+        let bytes = encode(&get_default_settings())?;
+        write_atomic(&settings_path, bytes)?;
+    }
+
+    let settings_bytes = fs::read(&settings_path)?;
+    decode(&settings_bytes)
+}
+
+fn store_settings(settings: Settings) -> Result<(), WhiskersError> {
+    let current_settings = match read_settings() {
+        Ok(settings) => settings,
+        Err(WhiskersError::Decode(_)) => get_default_settings(),
+        Err(error) => return Err(error),
+    };
 
     if current_settings.auto_start != settings.auto_start {
         #[cfg(target_os = "linux")]
@@ -57,26 +112,18 @@ Exec=whiskers-launcher-companion"#;
 
             let path = get_autostart_dir();
 
-            if !path.exists() {
-                fs::create_dir_all(&path).expect("Error creating autostart directory");
-            }
+            fs::create_dir_all(&path)?;
 
             let mut desktop_file_path = path.to_owned();
             desktop_file_path.push("whiskers-launcher.desktop");
 
             if settings.auto_start {
-                fs::write(&desktop_file_path, &desktop_content)
-                    .map_err(|_| ())
-                    .unwrap();
+                fs::write(&desktop_file_path, desktop_content)?;
 
                 // Gives read and write permissions so that it can be executed on autostart
-                fs::set_permissions(&desktop_file_path, fs::Permissions::from_mode(0o755))
-                    .map_err(|_| ())
-                    .unwrap();
-            } else {
-                if desktop_file_path.exists() {
-                    fs::remove_file(&desktop_file_path).map_err(|_| ()).unwrap();
-                }
+                fs::set_permissions(&desktop_file_path, fs::Permissions::from_mode(0o755))?;
+            } else if desktop_file_path.exists() {
+                fs::remove_file(&desktop_file_path)?;
             }
         }
 
@@ -89,17 +136,104 @@ Exec=whiskers-launcher-companion"#;
                 let mut target_path = get_app_dir();
                 target_path.push("whiskers-launcher-companion.exe");
 
-                let link = ShellLink::new(target_path).expect("Error initializing link");
+                let link = ShellLink::new(target_path)
+                    .map_err(|error| io::Error::other(error.to_string()))?;
 
-                link.create_lnk(shortcut_path).expect("Error creating link");
-            } else {
-                if shortcut_path.exists() {
-                    fs::remove_file(shortcut_path).expect("Error removing shortcut");
-                }
+                link.create_lnk(shortcut_path)
+                    .map_err(|error| io::Error::other(error.to_string()))?;
+            } else if shortcut_path.exists() {
+                fs::remove_file(shortcut_path)?;
             }
         }
     }
 
-    let bytes = bincode::serialize(&settings).expect("Error encoding settings");
-    fs::write(&get_settings_path(), bytes).expect("Error writing settings");
+    let bytes = encode(&settings)?;
+    write_atomic(get_settings_path(), bytes)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::{
+        protocol::{encode_version, PROTOCOL_VERSION},
+        testing::set_test_home,
+    };
+
+    use super::*;
+
+    #[test]
+    fn concurrent_updates_keep_every_change() {
+        let _home = set_test_home("settings-concurrent");
+
+        let writers = 16;
+
+        thread::scope(|scope| {
+            for writer in 0..writers {
+                scope.spawn(move || {
+                    try_update_settings(|settings| {
+                        settings.blacklist.push(format!("writer-{}", writer));
+                    })
+                    .unwrap();
+                });
+            }
+        });
+
+        let settings = try_get_settings().unwrap();
+
+        for writer in 0..writers {
+            assert!(settings.blacklist.contains(&format!("writer-{}", writer)));
+        }
+
+        assert_eq!(settings.blacklist.len(), writers);
+    }
+
+    #[test]
+    fn newer_settings_fall_back_without_overwriting() {
+        let _home = set_test_home("settings-newer");
+
+        // A file from a newer launcher has the same header with a higher version
+        let mut bytes = encode_version(&get_default_settings(), PROTOCOL_VERSION).unwrap();
+        bytes[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+
+        let path = get_settings_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &bytes).unwrap();
+
+        assert!(matches!(
+            try_get_settings(),
+            Err(WhiskersError::UnsupportedVersion { .. })
+        ));
+        assert_eq!(
+            get_settings().search_keyword,
+            get_default_settings().search_keyword
+        );
+        assert!(try_update_settings(|settings| settings.blacklist.clear()).is_err());
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn readers_share_the_lock() {
+        let _home = set_test_home("settings-shared");
+
+        // Creates the file so the reads below take the shared lock
+        try_get_settings().unwrap();
+
+        let _reader = lock_shared(get_settings_path()).unwrap();
+        assert!(try_get_settings().is_ok());
+    }
+
+    #[test]
+    fn missing_settings_are_created_with_the_defaults() {
+        let _home = set_test_home("settings-missing");
+
+        assert!(!get_settings_path().exists());
+        assert_eq!(
+            try_get_settings().unwrap().search_keyword,
+            get_default_settings().search_keyword
+        );
+        assert!(get_settings_path().exists());
+    }
 }
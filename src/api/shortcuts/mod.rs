@@ -0,0 +1,221 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::WhiskersError,
+    paths::{get_indexing_shortcuts_dir, get_indexing_shortcuts_path},
+    protocol::{decode, encode},
+    result::WLResult,
+    shortcut::Shortcut,
+    storage::{lock_exclusive, lock_shared, write_atomic},
+    utils::{fuzzy_score, get_search},
+};
+
+pub fn try_get_shortcuts() -> Result<Vec<Shortcut>, WhiskersError> {
+    let _lock = lock_shared(get_indexing_shortcuts_path())?;
+    read_shortcuts()
+}
+
+pub fn get_shortcuts() -> Vec<Shortcut> {
+    try_get_shortcuts().expect("Error getting shortcuts")
+}
+
+// Gives the shortcut the next id and copies its icon into the shortcuts dir. Returns the shortcut
+// as it was saved.
+pub fn try_add_shortcut(shortcut: Shortcut) -> Result<Shortcut, WhiskersError> {
+    let path = get_indexing_shortcuts_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut shortcuts = read_shortcuts()?;
+    check_keyword(&shortcuts, &shortcut.keyword, None)?;
+
+    let mut shortcut = shortcut;
+    shortcut.id = shortcuts
+        .iter()
+        .map(|shortcut| shortcut.id + 1)
+        .max()
+        .unwrap_or_default();
+
+    if let Some(icon) = &shortcut.icon {
+        shortcut.icon = Some(copy_icon(shortcut.id, icon)?);
+    }
+
+    shortcuts.push(shortcut.to_owned());
+    write_atomic(&path, encode(&shortcuts)?)?;
+
+    Ok(shortcut)
+}
+
+pub fn add_shortcut(shortcut: Shortcut) -> Shortcut {
+    try_add_shortcut(shortcut).expect("Error adding shortcut")
+}
+
+// Replaces the shortcut with the same id. An icon that changed is copied again and the old copy
+// is removed.
+pub fn try_update_shortcut(shortcut: Shortcut) -> Result<Shortcut, WhiskersError> {
+    let path = get_indexing_shortcuts_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut shortcuts = read_shortcuts()?;
+    check_keyword(&shortcuts, &shortcut.keyword, Some(shortcut.id))?;
+
+    let index = shortcuts
+        .iter()
+        .position(|saved| saved.id == shortcut.id)
+        .ok_or(WhiskersError::MissingShortcut(shortcut.id))?;
+
+    let mut shortcut = shortcut;
+
+    if let Some(icon) = &shortcut.icon {
+        shortcut.icon = Some(copy_icon(shortcut.id, icon)?);
+    }
+
+    if let Some(old_icon) = &shortcuts[index].icon {
+        if shortcut.icon.as_ref() != Some(old_icon) {
+            remove_icon(old_icon)?;
+        }
+    }
+
+    shortcuts[index] = shortcut.to_owned();
+    write_atomic(&path, encode(&shortcuts)?)?;
+
+    Ok(shortcut)
+}
+
+pub fn update_shortcut(shortcut: Shortcut) -> Shortcut {
+    try_update_shortcut(shortcut).expect("Error updating shortcut")
+}
+
+pub fn try_remove_shortcut(id: usize) -> Result<(), WhiskersError> {
+    let path = get_indexing_shortcuts_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut shortcuts = read_shortcuts()?;
+
+    let index = shortcuts
+        .iter()
+        .position(|shortcut| shortcut.id == id)
+        .ok_or(WhiskersError::MissingShortcut(id))?;
+
+    let shortcut = shortcuts.remove(index);
+
+    if let Some(icon) = &shortcut.icon {
+        remove_icon(icon)?;
+    }
+
+    write_atomic(&path, encode(&shortcuts)?)
+}
+
+pub fn remove_shortcut(id: usize) {
+    try_remove_shortcut(id).expect("Error removing shortcut");
+}
+
+// A text starting with a shortcut keyword gives that shortcut with the rest of the text as its
+// args. Otherwise the shortcuts are matched by name and keyword, best match first.
+pub fn try_get_shortcut_results(text: impl Into<String>) -> Result<Vec<WLResult>, WhiskersError> {
+    let text = text.into();
+    let shortcuts = try_get_shortcuts()?;
+    let search = get_search(&text);
+
+    if let Some(keyword) = &search.keyword {
+        if let Some(shortcut) = shortcuts
+            .iter()
+            .find(|shortcut| &shortcut.keyword == keyword)
+        {
+            return Ok(vec![shortcut.get_result(search.search_text)]);
+        }
+    }
+
+    let text = text.trim();
+
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut matches: Vec<(i64, &Shortcut)> = shortcuts
+        .iter()
+        .filter_map(|shortcut| {
+            let score =
+                fuzzy_score(&shortcut.name, text).max(fuzzy_score(&shortcut.keyword, text))?;
+
+            Some((score, shortcut))
+        })
+        .collect();
+
+    matches.sort_by(|(score, shortcut), (other_score, other_shortcut)| {
+        other_score
+            .cmp(score)
+            .then(shortcut.id.cmp(&other_shortcut.id))
+    });
+
+    Ok(matches
+        .into_iter()
+        .map(|(_, shortcut)| shortcut.get_result(""))
+        .collect())
+}
+
+pub fn get_shortcut_results(text: impl Into<String>) -> Vec<WLResult> {
+    try_get_shortcut_results(text).expect("Error getting shortcut results")
+}
+
+fn read_shortcuts() -> Result<Vec<Shortcut>, WhiskersError> {
+    match fs::read(get_indexing_shortcuts_path()) {
+        Ok(bytes) => decode(&bytes),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+// Keywords are unique between shortcuts. A new shortcut has no id yet.
+fn check_keyword(
+    shortcuts: &[Shortcut],
+    keyword: &str,
+    id: Option<usize>,
+) -> Result<(), WhiskersError> {
+    if shortcuts
+        .iter()
+        .any(|saved| saved.keyword == keyword && Some(saved.id) != id)
+    {
+        return Err(WhiskersError::KeywordTaken(keyword.to_owned()));
+    }
+
+    Ok(())
+}
+
+// Icons already in the shortcuts dir are kept, others are copied as <id>.<extension>
+fn copy_icon(id: usize, icon: &str) -> Result<String, WhiskersError> {
+    let icon_path = Path::new(icon);
+    let dir = get_indexing_shortcuts_dir();
+
+    if icon_path.parent() == Some(dir.as_path()) {
+        return Ok(icon.to_owned());
+    }
+
+    let mut path = dir.join(id.to_string());
+
+    if let Some(extension) = icon_path.extension() {
+        path.set_extension(extension);
+    }
+
+    fs::create_dir_all(&dir)?;
+    fs::copy(icon_path, &path)?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Only the copies in the shortcuts dir are removed, never the user files
+fn remove_icon(icon: &str) -> Result<(), WhiskersError> {
+    let icon_path = PathBuf::from(icon);
+
+    if icon_path.parent() != Some(get_indexing_shortcuts_dir().as_path()) {
+        return Ok(());
+    }
+
+    match fs::remove_file(icon_path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
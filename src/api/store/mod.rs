@@ -0,0 +1,552 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    error::{decode_error, WhiskersError},
+    paths::{get_extensions_dir, get_extensions_store_path, get_stores_dir},
+    storage::{lock_exclusive, lock_shared, write_atomic},
+    utils::fuzzy_score,
+};
+
+use super::extensions::{
+    try_get_extension_dir, try_get_extension_manifest, try_index_extensions, IndexingReport,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreExtension {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub repo: String,
+    pub version: String,
+    #[serde(default = "default_os")]
+    pub os: String,
+    #[serde(default)]
+    pub preview: Option<String>,
+}
+
+fn default_os() -> String {
+    "*".to_string()
+}
+
+impl StoreExtension {
+    // The os is "*" for every system or a comma separated list like "linux, windows"
+    pub fn is_compatible(&self) -> bool {
+        self.os.trim() == "*"
+            || self
+                .os
+                .split(',')
+                .any(|os| os.trim().eq_ignore_ascii_case(env::consts::OS))
+    }
+
+    pub fn is_installed(&self) -> bool {
+        try_get_extension_dir(&self.id).is_ok()
+    }
+}
+
+// Parses the catalog and checks what serde can't: ids are used as directory names so they must be
+// unique and plain, and every extension needs a repo to install from.
+pub fn parse_extensions_store(json: &str) -> Result<Vec<StoreExtension>, WhiskersError> {
+    let extensions: Vec<StoreExtension> = serde_json::from_str(json).map_err(decode_error)?;
+
+    for (index, extension) in extensions.iter().enumerate() {
+        let invalid = |message: &str| {
+            Err(WhiskersError::Decode(format!(
+                "Invalid store extension \"{}\": {}",
+                extension.id, message
+            )))
+        };
+
+        if extension.id.trim().is_empty() {
+            return invalid("the id is empty");
+        }
+
+        if extension.id == "." || extension.id == ".." || extension.id.contains(['/', '\\']) {
+            return invalid("the id can't be used as a directory name");
+        }
+
+        if extension.name.trim().is_empty() {
+            return invalid("the name is empty");
+        }
+
+        if extension.repo.trim().is_empty() {
+            return invalid("the repo is empty");
+        }
+
+        if extension.repo.starts_with('-') {
+            return invalid("the repo can't start with a dash");
+        }
+
+        if extensions[..index]
+            .iter()
+            .any(|other| other.id == extension.id)
+        {
+            return invalid("the id is used by another extension");
+        }
+    }
+
+    Ok(extensions)
+}
+
+pub fn try_get_store_extensions() -> Result<Vec<StoreExtension>, WhiskersError> {
+    let path = get_extensions_store_path();
+    let _lock = lock_shared(&path)?;
+
+    match fs::read_to_string(&path) {
+        Ok(json) => parse_extensions_store(&json),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn get_store_extensions() -> Vec<StoreExtension> {
+    try_get_store_extensions().expect("Error getting store extensions")
+}
+
+// Downloads the catalog from an http(s) or file:// url, or reads it from a local path, and saves
+// it as the extensions store once it's valid
+pub fn try_update_extensions_store(
+    source: impl Into<String>,
+) -> Result<Vec<StoreExtension>, WhiskersError> {
+    let json = read_store_source(&source.into())?;
+    let extensions = parse_extensions_store(&json)?;
+
+    let path = get_extensions_store_path();
+    let _lock = lock_exclusive(&path)?;
+    write_atomic(&path, json)?;
+
+    Ok(extensions)
+}
+
+pub fn update_extensions_store(source: impl Into<String>) -> Vec<StoreExtension> {
+    try_update_extensions_store(source).expect("Error updating extensions store")
+}
+
+// The compatible extensions matching the text by name, id or description, best match first. An
+// empty text keeps the catalog order.
+pub fn search_store_extensions(
+    extensions: &[StoreExtension],
+    text: impl Into<String>,
+) -> Vec<StoreExtension> {
+    let text = text.into();
+    let text = text.trim();
+
+    let mut matches: Vec<(i64, &StoreExtension)> = extensions
+        .iter()
+        .filter(|extension| extension.is_compatible())
+        .filter_map(|extension| {
+            if text.is_empty() {
+                return Some((0, extension));
+            }
+
+            let score = fuzzy_score(&extension.name, text)
+                .max(fuzzy_score(&extension.id, text))
+                .max(fuzzy_score(&extension.description, text).map(|score| score / 2))?;
+
+            Some((score, extension))
+        })
+        .collect();
+
+    matches.sort_by(|(score, _), (other_score, _)| other_score.cmp(score));
+
+    matches
+        .into_iter()
+        .map(|(_, extension)| extension.to_owned())
+        .collect()
+}
+
+pub fn try_install_extension(extension: &StoreExtension) -> Result<IndexingReport, WhiskersError> {
+    {
+        let extensions_dir = get_extensions_dir();
+        let _lock = lock_exclusive(&extensions_dir)?;
+
+        let extension_dir = extensions_dir.join(&extension.id);
+
+        // A dir without a valid manifest isn't indexed but moving into it would merge the two
+        if try_get_extension_dir(&extension.id).is_ok() || extension_dir.exists() {
+            return Err(WhiskersError::AlreadyInstalled(extension.id.to_owned()));
+        }
+
+        let download_dir = download_extension(extension)?;
+        let result = move_dir(&download_dir, &extension_dir);
+        remove_dir(&download_dir)?;
+        result?;
+    }
+
+    try_index_extensions()
+}
+
+pub fn install_extension(extension: &StoreExtension) -> IndexingReport {
+    try_install_extension(extension).expect("Error installing extension")
+}
+
+pub fn try_uninstall_extension(
+    extension_id: impl Into<String>,
+) -> Result<IndexingReport, WhiskersError> {
+    {
+        let _lock = lock_exclusive(get_extensions_dir())?;
+        fs::remove_dir_all(try_get_extension_dir(extension_id)?)?;
+    }
+
+    try_index_extensions()
+}
+
+pub fn uninstall_extension(extension_id: impl Into<String>) -> IndexingReport {
+    try_uninstall_extension(extension_id).expect("Error uninstalling extension")
+}
+
+// Downloads the extension again and swaps it with the installed one. The installed extension is
+// only removed after the download succeeded.
+pub fn try_update_extension(extension: &StoreExtension) -> Result<IndexingReport, WhiskersError> {
+    {
+        let _lock = lock_exclusive(get_extensions_dir())?;
+        let extension_dir = try_get_extension_dir(&extension.id)?;
+
+        let download_dir = download_extension(extension)?;
+        let result = replace_dir(&download_dir, &extension_dir, &extension.id);
+        remove_dir(&download_dir)?;
+        result?;
+    }
+
+    try_index_extensions()
+}
+
+pub fn update_extension(extension: &StoreExtension) -> IndexingReport {
+    try_update_extension(extension).expect("Error updating extension")
+}
+
+// Fetches the repo into a temporary dir of the stores dir and checks that it has the manifest of
+// the extension. Local dirs and file:// urls are copied and anything else is cloned with git.
+fn download_extension(extension: &StoreExtension) -> Result<PathBuf, WhiskersError> {
+    let download_dir =
+        get_stores_dir().join(format!(".{}-{}.download", extension.id, process::id()));
+
+    remove_dir(&download_dir)?;
+
+    let result = fetch_repo(&extension.repo, &download_dir).and_then(|_| {
+        let manifest = try_get_extension_manifest(&download_dir)?;
+
+        if manifest.id != extension.id {
+            return Err(WhiskersError::Decode(format!(
+                "Store extension \"{}\" has a manifest for \"{}\"",
+                extension.id, manifest.id
+            )));
+        }
+
+        Ok(download_dir.to_owned())
+    });
+
+    if result.is_err() {
+        remove_dir(&download_dir)?;
+    }
+
+    result
+}
+
+fn fetch_repo(repo: &str, dir: &Path) -> Result<(), WhiskersError> {
+    let local_path = get_local_path(repo);
+
+    if local_path.is_dir() {
+        return copy_dir(&local_path, dir);
+    }
+
+    let output = Command::new("git")
+        .arg("clone")
+        .arg("--depth")
+        .arg("1")
+        // Keeps a repo starting with a dash from being read as an option
+        .arg("--")
+        .arg(repo)
+        .arg(dir)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(WhiskersError::Io(io::Error::other(format!(
+            "git clone of {} failed: {}",
+            repo,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+
+    remove_dir(&dir.join(".git"))
+}
+
+// Reads a catalog from an http(s) or file:// url or from a local path
+pub(crate) fn read_store_source(source: &str) -> Result<String, WhiskersError> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        return read_http_source(source);
+    }
+
+    Ok(fs::read_to_string(get_local_path(source))?)
+}
+
+#[cfg(feature = "http")]
+fn read_http_source(source: &str) -> Result<String, WhiskersError> {
+    ureq::get(source)
+        .call()
+        .map_err(crate::error::http_error)?
+        .into_string()
+        .map_err(WhiskersError::from)
+}
+
+#[cfg(not(feature = "http"))]
+fn read_http_source(source: &str) -> Result<String, WhiskersError> {
+    Err(WhiskersError::Http(format!(
+        "Reading {} needs the http feature",
+        source
+    )))
+}
+
+fn get_local_path(source: &str) -> PathBuf {
+    PathBuf::from(source.strip_prefix("file://").unwrap_or(source))
+}
+
+// Skips the git metadata of local repos
+fn copy_dir(from: &Path, to: &Path) -> Result<(), WhiskersError> {
+    for entry in WalkDir::new(from)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+    {
+        let entry = entry.map_err(io::Error::from)?;
+        let relative_path = entry.path().strip_prefix(from).unwrap_or(entry.path());
+        let path = to.join(relative_path);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&path)?;
+        } else {
+            fs::copy(entry.path(), &path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Renames when possible and copies when the dirs are on different file systems
+fn move_dir(from: &Path, to: &Path) -> Result<(), WhiskersError> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_dir(from, to)
+}
+
+// Moves the installed dir aside before moving the new one in so that it can be put back when the
+// move fails
+fn replace_dir(from: &Path, to: &Path, extension_id: &str) -> Result<(), WhiskersError> {
+    let backup_dir = get_stores_dir().join(format!(".{}-{}.backup", extension_id, process::id()));
+
+    remove_dir(&backup_dir)?;
+    move_dir(to, &backup_dir)?;
+
+    if let Err(error) = move_dir(from, to) {
+        remove_dir(to)?;
+        move_dir(&backup_dir, to)?;
+        remove_dir(&backup_dir)?;
+        return Err(error);
+    }
+
+    remove_dir(&backup_dir)
+}
+
+fn remove_dir(dir: &Path) -> Result<(), WhiskersError> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::extensions::get_extensions, testing::set_test_home};
+
+    use super::*;
+
+    fn get_catalog_entry(id: &str, repo: &str) -> String {
+        format!(
+            r#"{{"id": "{}", "name": "Name", "description": "", "repo": "{}", "version": "1"}}"#,
+            id, repo
+        )
+    }
+
+    // A local repo with the manifest and a file whose content tells the versions apart
+    fn create_repo(root: &Path, id: &str, version: &str) -> PathBuf {
+        let repo = root.join("repos").join(id);
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("HEAD"), "ref").unwrap();
+        fs::write(
+            repo.join("manifest.json"),
+            format!(
+                r#"{{"id": "{}", "name": "Hello", "description": "Says hi", "keyword": "hi"}}"#,
+                id
+            ),
+        )
+        .unwrap();
+        fs::write(repo.join("version.txt"), version).unwrap();
+        repo
+    }
+
+    fn get_store_extension(repo: &Path, id: &str) -> StoreExtension {
+        let catalog = format!(
+            "[{}]",
+            get_catalog_entry(id, &format!("file://{}", repo.display()))
+        );
+
+        parse_extensions_store(&catalog).unwrap().remove(0)
+    }
+
+    #[test]
+    fn catalog_rejects_unsafe_ids_and_repos() {
+        let invalid = [
+            format!("[{}]", get_catalog_entry("..", "repo")),
+            format!("[{}]", get_catalog_entry("a/b", "repo")),
+            format!("[{}]", get_catalog_entry("a\\\\b", "repo")),
+            format!("[{}]", get_catalog_entry("a", "-repo")),
+            format!("[{}]", get_catalog_entry(" ", "repo")),
+            format!(
+                "[{}, {}]",
+                get_catalog_entry("a", "repo"),
+                get_catalog_entry("a", "other")
+            ),
+        ];
+
+        for catalog in invalid {
+            assert!(
+                matches!(
+                    parse_extensions_store(&catalog),
+                    Err(WhiskersError::Decode(_))
+                ),
+                "{}",
+                catalog
+            );
+        }
+
+        let catalog = format!("[{}]", get_catalog_entry("a.b-c", "https://example.com/a"));
+        assert_eq!(parse_extensions_store(&catalog).unwrap()[0].os, "*");
+    }
+
+    #[test]
+    fn catalog_is_read_from_a_file_url() {
+        let home = set_test_home("store-catalog");
+        let catalog_path = home.path.join("catalog.json");
+        fs::write(
+            &catalog_path,
+            format!("[{}]", get_catalog_entry("hello", "repo")),
+        )
+        .unwrap();
+
+        let extensions =
+            try_update_extensions_store(format!("file://{}", catalog_path.display())).unwrap();
+
+        assert_eq!(extensions[0].id, "hello");
+        assert_eq!(try_get_store_extensions().unwrap()[0].id, "hello");
+    }
+
+    #[test]
+    fn install_update_and_uninstall_from_a_local_repo() {
+        let home = set_test_home("store-install");
+        let repo = create_repo(&home.path, "hello", "1");
+        let extension = get_store_extension(&repo, "hello");
+
+        let report = try_install_extension(&extension).unwrap();
+        assert!(!report.has_errors());
+        assert!(extension.is_installed());
+
+        let dir = try_get_extension_dir("hello").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("version.txt")).unwrap(), "1");
+        assert!(!dir.join(".git").exists());
+
+        fs::write(repo.join("version.txt"), "2").unwrap();
+        try_update_extension(&extension).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("version.txt")).unwrap(), "2");
+
+        try_uninstall_extension("hello").unwrap();
+        assert!(!extension.is_installed());
+        assert!(get_extensions().is_empty());
+
+        // Nothing is left behind in the stores dir
+        let leftovers: Vec<_> = fs::read_dir(get_stores_dir())
+            .map(|entries| entries.flatten().map(|entry| entry.file_name()).collect())
+            .unwrap_or_default();
+        assert!(leftovers
+            .iter()
+            .all(|name| !name.to_string_lossy().starts_with('.')));
+    }
+
+    #[test]
+    fn duplicate_install_is_rejected() {
+        let home = set_test_home("store-duplicate");
+        let repo = create_repo(&home.path, "hello", "1");
+        let extension = get_store_extension(&repo, "hello");
+
+        try_install_extension(&extension).unwrap();
+
+        assert!(matches!(
+            try_install_extension(&extension),
+            Err(WhiskersError::AlreadyInstalled(id)) if id == "hello"
+        ));
+    }
+
+    #[test]
+    fn install_into_an_unindexed_dir_is_rejected() {
+        let home = set_test_home("store-unindexed");
+        let repo = create_repo(&home.path, "hello", "1");
+        let extension = get_store_extension(&repo, "hello");
+
+        let dir = get_extensions_dir().join("hello");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "mine").unwrap();
+
+        assert!(matches!(
+            try_install_extension(&extension),
+            Err(WhiskersError::AlreadyInstalled(_))
+        ));
+        assert!(!dir.join("manifest.json").exists());
+    }
+
+    #[test]
+    fn failed_install_leaves_nothing_behind() {
+        let home = set_test_home("store-mismatch");
+        let repo = create_repo(&home.path, "other", "1");
+        let extension = get_store_extension(&repo, "hello");
+
+        assert!(matches!(
+            try_install_extension(&extension),
+            Err(WhiskersError::Decode(_))
+        ));
+        assert!(!get_extensions_dir().join("hello").exists());
+    }
+
+    #[test]
+    fn failed_replace_restores_the_old_dir() {
+        let home = set_test_home("store-restore");
+        let installed = home.path.join("installed");
+        fs::create_dir_all(&installed).unwrap();
+        fs::write(installed.join("version.txt"), "1").unwrap();
+
+        let missing = home.path.join("missing");
+
+        assert!(replace_dir(&missing, &installed, "hello").is_err());
+        assert_eq!(
+            fs::read_to_string(installed.join("version.txt")).unwrap(),
+            "1"
+        );
+
+        let backups: Vec<_> = fs::read_dir(get_stores_dir())
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".backup"))
+            .collect();
+        assert!(backups.is_empty());
+    }
+}
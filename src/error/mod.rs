@@ -0,0 +1,104 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum WhiskersError {
+    Io(io::Error),
+    Decode(String),
+    Encode(String),
+    MissingManifest(PathBuf),
+    MissingExtension(String),
+    InvalidSetting {
+        extension_id: String,
+        setting_id: String,
+    },
+    UnsupportedVersion {
+        version: u16,
+        supported: u16,
+    },
+    Http(String),
+    MissingShortcut(usize),
+    KeywordTaken(String),
+    AlreadyInstalled(String),
+    MissingTheme(String),
+    InvalidSession(String),
+    InvalidColor {
+        name: String,
+        color: String,
+    },
+}
+
+impl fmt::Display for WhiskersError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WhiskersError::Io(error) => write!(f, "I/O error: {}", error),
+            WhiskersError::Decode(message) => write!(f, "Error decoding data: {}", message),
+            WhiskersError::Encode(message) => write!(f, "Error encoding data: {}", message),
+            WhiskersError::MissingManifest(path) => {
+                write!(f, "Missing extension manifest: {}", path.display())
+            }
+            WhiskersError::MissingExtension(extension_id) => {
+                write!(f, "Extension not found: {}", extension_id)
+            }
+            WhiskersError::InvalidSetting {
+                extension_id,
+                setting_id,
+            } => write!(
+                f,
+                "Invalid setting \"{}\" for extension \"{}\"",
+                setting_id, extension_id
+            ),
+            WhiskersError::UnsupportedVersion { version, supported } => write!(
+                f,
+                "Unsupported protocol version {} (supported up to {})",
+                version, supported
+            ),
+            WhiskersError::Http(message) => write!(f, "HTTP error: {}", message),
+            WhiskersError::MissingShortcut(id) => write!(f, "Shortcut not found: {}", id),
+            WhiskersError::KeywordTaken(keyword) => {
+                write!(
+                    f,
+                    "Keyword \"{}\" is already used by another shortcut",
+                    keyword
+                )
+            }
+            WhiskersError::AlreadyInstalled(extension_id) => {
+                write!(f, "Extension already installed: {}", extension_id)
+            }
+            WhiskersError::MissingTheme(theme_id) => write!(f, "Theme not found: {}", theme_id),
+            WhiskersError::InvalidSession(session_id) => {
+                write!(f, "Invalid session id: \"{}\"", session_id)
+            }
+            WhiskersError::InvalidColor { name, color } => {
+                write!(f, "Invalid color \"{}\" for {}", color, name)
+            }
+        }
+    }
+}
+
+impl Error for WhiskersError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WhiskersError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WhiskersError {
+    fn from(error: io::Error) -> Self {
+        WhiskersError::Io(error)
+    }
+}
+
+pub(crate) fn decode_error(error: impl fmt::Display) -> WhiskersError {
+    WhiskersError::Decode(error.to_string())
+}
+
+pub(crate) fn encode_error(error: impl fmt::Display) -> WhiskersError {
+    WhiskersError::Encode(error.to_string())
+}
+
+#[cfg(feature = "http")]
+pub(crate) fn http_error(error: impl fmt::Display) -> WhiskersError {
+    WhiskersError::Http(error.to_string())
+}
@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    protocol::{WireFormat, LEGACY_PROTOCOL_VERSION},
+    settings::SettingValue,
+};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Extension {
     pub id: String,
@@ -10,6 +15,18 @@ pub struct Extension {
     pub settings: Option<Vec<ExtensionSetting>>,
     #[serde(default = "default_os")]
     pub os: String,
+    #[serde(default = "default_mode")]
+    pub mode: ExtensionMode,
+    #[serde(default = "default_api_version")]
+    pub api_version: u16,
+    #[serde(default = "default_protocol")]
+    pub protocol: WireFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ExtensionMode {
+    OneShot,
+    Persistent,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,7 +35,7 @@ pub struct ExtensionSetting {
     pub title: String,
     pub description: String,
     pub setting_type: SettingType,
-    pub default_value: String,
+    pub default_value: SettingValue,
     #[serde(default = "default_show_conditions")]
     pub show_conditions: Option<Vec<ShowCondition>>,
     #[serde(default = "default_select_options")]
@@ -33,6 +50,9 @@ pub enum SettingType {
     TextArea,
     Select,
     Toggle,
+    Number,
+    Path,
+    List,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -55,6 +75,18 @@ fn default_os() -> String {
     "*".to_string()
 }
 
+fn default_mode() -> ExtensionMode {
+    ExtensionMode::OneShot
+}
+
+fn default_api_version() -> u16 {
+    LEGACY_PROTOCOL_VERSION
+}
+
+fn default_protocol() -> WireFormat {
+    WireFormat::Bincode
+}
+
 fn default_show_conditions() -> Option<Vec<ShowCondition>> {
     None
 }
@@ -0,0 +1,456 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::WhiskersError;
+
+const DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+// Field codes that expand to files, urls or launcher details which don't exist when running the
+// app from the launcher. Deprecated codes are removed as the spec asks.
+const FIELD_CODES: [char; 13] = [
+    'f', 'F', 'u', 'U', 'd', 'D', 'n', 'N', 'i', 'c', 'k', 'v', 'm',
+];
+
+#[derive(Debug, Clone)]
+pub struct DesktopEntry {
+    pub path: PathBuf,
+    entries: HashMap<String, String>,
+}
+
+impl DesktopEntry {
+    pub fn parse(path: impl AsRef<Path>) -> Result<Self, WhiskersError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+
+        Ok(Self::parse_str(path, &content))
+    }
+
+    // Only the keys of the [Desktop Entry] group are kept since actions and other groups aren't
+    // indexed
+    pub fn parse_str(path: impl AsRef<Path>, content: &str) -> Self {
+        let mut entries = HashMap::<String, String>::new();
+        let mut in_entry_group = false;
+
+        for line in content.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                in_entry_group = &line[1..line.len() - 1] == DESKTOP_ENTRY_GROUP;
+                continue;
+            }
+
+            if !in_entry_group {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                entries
+                    .entry(key.trim().to_owned())
+                    .or_insert(unescape_value(value.trim()));
+            }
+        }
+
+        Self {
+            path: path.as_ref().to_owned(),
+            entries,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(|value| value.as_str())
+    }
+
+    pub fn get_bool(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|value| value == "true")
+    }
+
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .split(';')
+                    .map(|item| item.trim().to_owned())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Looks for `key[locale]` in the order the spec gives, lang_COUNTRY@MODIFIER, lang_COUNTRY,
+    // lang@MODIFIER and lang, before falling back to the plain key
+    pub fn get_localized(&self, key: &str, locale: Option<&str>) -> Option<&str> {
+        if let Some(locale) = locale {
+            for candidate in get_locale_candidates(locale) {
+                if let Some(value) = self.get(&format!("{}[{}]", key, candidate)) {
+                    return Some(value);
+                }
+            }
+        }
+
+        self.get(key)
+    }
+
+    pub fn is_application(&self) -> bool {
+        self.get("Type") == Some("Application")
+    }
+
+    // Checks NoDisplay, Hidden, OnlyShowIn and NotShowIn against the current desktops
+    pub fn is_visible(&self, current_desktops: &[String]) -> bool {
+        if self.get_bool("NoDisplay") || self.get_bool("Hidden") {
+            return false;
+        }
+
+        let only_show_in = self.get_list("OnlyShowIn");
+
+        if !only_show_in.is_empty()
+            && !only_show_in
+                .iter()
+                .any(|desktop| current_desktops.contains(desktop))
+        {
+            return false;
+        }
+
+        !self
+            .get_list("NotShowIn")
+            .iter()
+            .any(|desktop| current_desktops.contains(desktop))
+    }
+
+    // An entry with a TryExec whose binary can't be found belongs to an app that isn't installed
+    pub fn is_installed(&self) -> bool {
+        match self.get("TryExec") {
+            Some(try_exec) => find_executable(try_exec).is_some(),
+            None => true,
+        }
+    }
+
+    pub fn get_command(&self) -> Option<String> {
+        self.get("Exec")
+            .map(strip_field_codes)
+            .filter(|command| !command.is_empty())
+    }
+}
+
+pub fn get_current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(|desktop| desktop.to_owned())
+        .collect()
+}
+
+// The messages locale without its encoding, e.g. "pt_PT@euro" for "pt_PT.UTF-8@euro"
+pub fn get_current_locale() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|value| !value.is_empty())?;
+
+    if locale == "C" || locale == "POSIX" {
+        return None;
+    }
+
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale.to_owned(), Some(modifier.to_owned())),
+        None => (locale, None),
+    };
+
+    let locale = locale.split('.').next().unwrap_or_default().to_owned();
+
+    match modifier {
+        Some(modifier) => Some(format!("{}@{}", locale, modifier)),
+        None => Some(locale),
+    }
+}
+
+fn get_locale_candidates(locale: &str) -> Vec<String> {
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+
+    let mut candidates = Vec::<String>::new();
+
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        candidates.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+
+    if let Some(country) = country {
+        candidates.push(format!("{}_{}", lang, country));
+    }
+
+    if let Some(modifier) = modifier {
+        candidates.push(format!("{}@{}", lang, modifier));
+    }
+
+    candidates.push(lang.to_owned());
+    candidates
+}
+
+fn unescape_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            unescaped.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+// Splits the Exec value into its arguments, drops the field codes and joins it back, quoting
+// the arguments that need it
+pub fn strip_field_codes(exec: &str) -> String {
+    split_exec(exec)
+        .into_iter()
+        .filter_map(|argument| {
+            let mut stripped = String::with_capacity(argument.len());
+            let mut chars = argument.chars().peekable();
+            let mut had_field_code = false;
+
+            while let Some(char) = chars.next() {
+                if char != '%' {
+                    stripped.push(char);
+                    continue;
+                }
+
+                match chars.peek() {
+                    Some('%') => {
+                        stripped.push('%');
+                        chars.next();
+                    }
+                    Some(code) if FIELD_CODES.contains(code) => {
+                        had_field_code = true;
+                        chars.next();
+                    }
+                    _ => stripped.push('%'),
+                }
+            }
+
+            if stripped.is_empty() && had_field_code {
+                None
+            } else {
+                Some(quote_argument(&stripped))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn split_exec(exec: &str) -> Vec<String> {
+    let mut arguments = Vec::<String>::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_argument = true;
+            }
+            '\\' if in_quotes => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            char if char.is_whitespace() && !in_quotes => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            char => {
+                current.push(char);
+                in_argument = true;
+            }
+        }
+    }
+
+    if in_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+fn quote_argument(argument: &str) -> String {
+    let needs_quotes = argument.is_empty()
+        || argument
+            .chars()
+            .any(|char| char.is_whitespace() || "\"'\\$`;&|<>()*?#~".contains(char));
+
+    if !needs_quotes {
+        return argument.to_owned();
+    }
+
+    let mut quoted = String::from("\"");
+
+    for char in argument.chars() {
+        if "\"`$\\".contains(char) {
+            quoted.push('\\');
+        }
+
+        quoted.push(char);
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+
+    if path.is_absolute() {
+        return path.is_file().then(|| path.to_owned());
+    }
+
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_entry(content: &str) -> DesktopEntry {
+        DesktopEntry::parse_str(
+            "/usr/share/applications/app.desktop",
+            &format!("[Desktop Entry]\nType=Application\nName=App\n{}", content),
+        )
+    }
+
+    #[test]
+    fn field_codes_are_stripped() {
+        let cases = [
+            ("firefox %u", "firefox"),
+            ("app %f %F %u %U %i %c %k", "app"),
+            ("app --file=%f --name %c", "app --file= --name"),
+            ("app %d %D %n %N %v %m", "app"),
+            ("app 100%%", "app 100%"),
+            ("app %x", "app %x"),
+            ("%U", ""),
+        ];
+
+        for (exec, command) in cases {
+            assert_eq!(strip_field_codes(exec), command, "{}", exec);
+        }
+    }
+
+    #[test]
+    fn arguments_are_quoted_again() {
+        let cases = [
+            (r#""/opt/My App/app" %F"#, r#""/opt/My App/app""#),
+            (
+                r#"sh -c "echo \"hi\" \$HOME""#,
+                r#"sh -c "echo \"hi\" \$HOME""#,
+            ),
+            (r#"app "100%% done" %c"#, r#"app "100% done""#),
+            (r#"app "--title=%c is open""#, r#"app "--title= is open""#),
+            (r#"app """#, r#"app """#),
+            ("app 'single'", r#"app "'single'""#),
+        ];
+
+        for (exec, command) in cases {
+            assert_eq!(strip_field_codes(exec), command, "{}", exec);
+        }
+    }
+
+    #[test]
+    fn exec_values_are_unescaped_before_splitting() {
+        let entry = get_entry(r#"Exec=app "a\sb" c\sd %u"#);
+        assert_eq!(entry.get_command().as_deref(), Some(r#"app "a b" c d"#));
+
+        assert_eq!(get_entry("Exec=%U").get_command(), None);
+        assert_eq!(get_entry("").get_command(), None);
+    }
+
+    #[test]
+    fn hidden_entries_are_not_visible() {
+        let desktops = [String::from("GNOME")];
+
+        assert!(get_entry("").is_visible(&desktops));
+        assert!(!get_entry("NoDisplay=true").is_visible(&desktops));
+        assert!(!get_entry("Hidden=true").is_visible(&desktops));
+        assert!(get_entry("Hidden=false").is_visible(&desktops));
+    }
+
+    #[test]
+    fn desktop_lists_are_checked() {
+        let gnome = [String::from("ubuntu"), String::from("GNOME")];
+        let kde = [String::from("KDE")];
+
+        let only_gnome = get_entry("OnlyShowIn=GNOME;Unity;");
+        assert!(only_gnome.is_visible(&gnome));
+        assert!(!only_gnome.is_visible(&kde));
+        assert!(!only_gnome.is_visible(&[]));
+
+        let not_kde = get_entry("NotShowIn=KDE;");
+        assert!(not_kde.is_visible(&gnome));
+        assert!(!not_kde.is_visible(&kde));
+    }
+
+    #[test]
+    fn try_exec_has_to_exist() {
+        let executable = env::current_exe().unwrap();
+
+        assert!(get_entry("").is_installed());
+        assert!(get_entry(&format!("TryExec={}", executable.display())).is_installed());
+        assert!(!get_entry("TryExec=/nonexistent/whiskers-app").is_installed());
+        assert!(!get_entry("TryExec=whiskers-app-that-does-not-exist").is_installed());
+    }
+
+    #[test]
+    fn other_groups_and_localized_keys() {
+        let entry = DesktopEntry::parse_str(
+            "app.desktop",
+            "# Comment\n[Desktop Action new]\nName=New\n[Desktop Entry]\nName=App\n\
+             Name[pt]=Aplicação\nName[pt_BR]=Aplicativo\nName=Ignored\n",
+        );
+
+        assert_eq!(entry.get("Name"), Some("App"));
+        assert_eq!(
+            entry.get_localized("Name", Some("pt_BR")),
+            Some("Aplicativo")
+        );
+        assert_eq!(
+            entry.get_localized("Name", Some("pt_PT@euro")),
+            Some("Aplicação")
+        );
+        assert_eq!(entry.get_localized("Name", Some("de")), Some("App"));
+        assert!(!entry.is_application());
+    }
+}
@@ -0,0 +1,715 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::WhiskersError,
+    indexing::desktop::DesktopEntry,
+    paths::{get_data_dirs, get_home_dir, get_indexing_icons_dir},
+    storage::write_atomic,
+};
+
+pub const DEFAULT_ICON_SIZE: u32 = 48;
+pub const FALLBACK_ICON_THEME: &str = "hicolor";
+pub const ICON_THEME_ENV: &str = "WHISKERS_ICON_THEME";
+
+const ICON_EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+// Most UIs can't draw xpm so it's only used when no theme has the icon in another format
+const LEGACY_ICON_EXTENSIONS: [&str; 1] = ["xpm"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum DirectoryType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+#[derive(Debug, Clone)]
+struct ThemeDirectory {
+    path: String,
+    size: u32,
+    scale: u32,
+    directory_type: DirectoryType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+#[derive(Debug, Clone)]
+struct IconTheme {
+    dirs: Vec<PathBuf>,
+    inherits: Vec<String>,
+    directories: Vec<ThemeDirectory>,
+}
+
+// Resolves freedesktop icon names following the Icon Theme Specification
+#[derive(Debug, Clone)]
+pub struct IconResolver {
+    pub theme: String,
+    pub size: u32,
+    pub scale: u32,
+    pub base_dirs: Vec<PathBuf>,
+    themes: HashMap<String, Option<IconTheme>>,
+}
+
+impl ThemeDirectory {
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+
+        match self.directory_type {
+            DirectoryType::Fixed => self.size == size,
+            DirectoryType::Scalable => self.min_size <= size && size <= self.max_size,
+            DirectoryType::Threshold => {
+                self.size.saturating_sub(self.threshold) <= size
+                    && size <= self.size + self.threshold
+            }
+        }
+    }
+
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let size = size * scale;
+
+        let (min, max) = match self.directory_type {
+            DirectoryType::Fixed => (self.size * self.scale, self.size * self.scale),
+            DirectoryType::Scalable => (self.min_size * self.scale, self.max_size * self.scale),
+            DirectoryType::Threshold => (
+                self.size.saturating_sub(self.threshold) * self.scale,
+                (self.size + self.threshold) * self.scale,
+            ),
+        };
+
+        if size < min {
+            min - size
+        } else {
+            size.saturating_sub(max)
+        }
+    }
+}
+
+impl IconResolver {
+    pub fn new() -> Self {
+        Self {
+            theme: get_icon_theme_name(),
+            size: DEFAULT_ICON_SIZE,
+            scale: 1,
+            base_dirs: get_icon_base_dirs(),
+            themes: HashMap::new(),
+        }
+    }
+
+    pub fn theme(&mut self, theme: impl Into<String>) -> Self {
+        self.theme = theme.into();
+        self.to_owned()
+    }
+
+    pub fn size(&mut self, size: u32) -> Self {
+        self.size = size;
+        self.to_owned()
+    }
+
+    pub fn scale(&mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self.to_owned()
+    }
+
+    pub fn base_dirs(&mut self, base_dirs: Vec<PathBuf>) -> Self {
+        self.base_dirs = base_dirs;
+        self.themes.clear();
+        self.to_owned()
+    }
+
+    // Looks the icon up in the current theme, the themes it inherits from, hicolor and finally
+    // the unthemed icons
+    pub fn resolve(&mut self, icon: impl Into<String>) -> Option<PathBuf> {
+        let icon = icon.into();
+
+        if icon.is_empty() {
+            return None;
+        }
+
+        let path = Path::new(&icon);
+
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_owned());
+        }
+
+        self.find(&icon, &ICON_EXTENSIONS)
+            .or_else(|| self.find(&icon, &LEGACY_ICON_EXTENSIONS))
+    }
+
+    // Resolves the icon and copies it into the indexing icons dir so the UI gets a stable
+    // absolute path. The name has a hash of the resolved path so icons with the same name from
+    // different dirs don't replace each other.
+    pub fn try_cache(&mut self, icon: impl Into<String>) -> Result<Option<PathBuf>, WhiskersError> {
+        let icon = icon.into();
+
+        let resolved = match self.resolve(&icon) {
+            Some(resolved) => resolved,
+            None => return Ok(None),
+        };
+
+        let extension = resolved
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or(String::from("png"));
+
+        let name = Path::new(&icon)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or(icon.to_owned());
+
+        let name: String = name
+            .chars()
+            .map(|char| {
+                if char.is_alphanumeric() || "-_.".contains(char) {
+                    char
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        let mut cached_path = get_indexing_icons_dir();
+        cached_path.push(format!(
+            "{}-{}@{}-{:016x}.{}",
+            name,
+            self.size,
+            self.scale,
+            get_path_hash(&resolved),
+            extension
+        ));
+
+        if !is_cache_up_to_date(&cached_path, &resolved) {
+            write_atomic(&cached_path, fs::read(&resolved)?)?;
+        }
+
+        Ok(Some(cached_path))
+    }
+
+    pub fn cache(&mut self, icon: impl Into<String>) -> Option<PathBuf> {
+        self.try_cache(icon).expect("Error caching icon")
+    }
+
+    fn find(&mut self, icon: &str, extensions: &[&str]) -> Option<PathBuf> {
+        let mut visited = HashSet::<String>::new();
+
+        if let Some(path) =
+            self.find_in_theme(&self.theme.to_owned(), icon, extensions, &mut visited)
+        {
+            return Some(path);
+        }
+
+        if !visited.contains(FALLBACK_ICON_THEME) {
+            if let Some(path) =
+                self.find_in_theme(FALLBACK_ICON_THEME, icon, extensions, &mut visited)
+            {
+                return Some(path);
+            }
+        }
+
+        self.find_fallback(icon, extensions)
+    }
+
+    fn find_in_theme(
+        &mut self,
+        theme_name: &str,
+        icon: &str,
+        extensions: &[&str],
+        visited: &mut HashSet<String>,
+    ) -> Option<PathBuf> {
+        if !visited.insert(theme_name.to_owned()) {
+            return None;
+        }
+
+        let theme = self.load_theme(theme_name)?;
+
+        if let Some(path) = self.lookup_icon(&theme, icon, extensions) {
+            return Some(path);
+        }
+
+        for parent in &theme.inherits {
+            if let Some(path) = self.find_in_theme(parent, icon, extensions, visited) {
+                return Some(path);
+            }
+        }
+
+        None
+    }
+
+    fn lookup_icon(&self, theme: &IconTheme, icon: &str, extensions: &[&str]) -> Option<PathBuf> {
+        for directory in &theme.directories {
+            if !directory.matches_size(self.size, self.scale) {
+                continue;
+            }
+
+            if let Some(path) = find_icon_file(&theme.dirs, &directory.path, icon, extensions) {
+                return Some(path);
+            }
+        }
+
+        let mut closest: Option<(u32, PathBuf)> = None;
+
+        for directory in &theme.directories {
+            let distance = directory.size_distance(self.size, self.scale);
+
+            if closest
+                .as_ref()
+                .is_some_and(|(closest_distance, _)| *closest_distance <= distance)
+            {
+                continue;
+            }
+
+            if let Some(path) = find_icon_file(&theme.dirs, &directory.path, icon, extensions) {
+                closest = Some((distance, path));
+            }
+        }
+
+        closest.map(|(_, path)| path)
+    }
+
+    fn find_fallback(&self, icon: &str, extensions: &[&str]) -> Option<PathBuf> {
+        for dir in &self.base_dirs {
+            for extension in extensions {
+                let path = dir.join(format!("{}.{}", icon, extension));
+
+                if path.is_file() {
+                    return Some(path);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn load_theme(&mut self, theme_name: &str) -> Option<IconTheme> {
+        if let Some(theme) = self.themes.get(theme_name) {
+            return theme.to_owned();
+        }
+
+        let theme = read_theme(&self.base_dirs, theme_name);
+        self.themes.insert(theme_name.to_owned(), theme.to_owned());
+        theme
+    }
+}
+
+impl Default for IconResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// $HOME/.icons, the icons dir of every data dir and /usr/share/pixmaps, in lookup order
+pub fn get_icon_base_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![get_home_dir().join(".icons")];
+    dirs.extend(get_data_dirs().into_iter().map(|dir| dir.join("icons")));
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    dirs
+}
+
+// Uses the theme from the environment, then the GTK and KDE settings, then hicolor
+pub fn get_icon_theme_name() -> String {
+    if let Some(theme) = env::var(ICON_THEME_ENV)
+        .ok()
+        .filter(|theme| !theme.is_empty())
+    {
+        return theme;
+    }
+
+    let config_dir = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or(get_home_dir().join(".config"));
+
+    let candidates = [
+        (
+            config_dir.join("gtk-4.0/settings.ini"),
+            "gtk-icon-theme-name",
+        ),
+        (
+            config_dir.join("gtk-3.0/settings.ini"),
+            "gtk-icon-theme-name",
+        ),
+        (config_dir.join("kdeglobals"), "Theme"),
+    ];
+
+    for (path, key) in candidates {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let theme = content.lines().find_map(|line| {
+            let (line_key, value) = line.split_once('=')?;
+
+            if line_key.trim() == key {
+                Some(value.trim().trim_matches('"').to_owned())
+            } else {
+                None
+            }
+        });
+
+        if let Some(theme) = theme.filter(|theme| !theme.is_empty()) {
+            return theme;
+        }
+    }
+
+    String::from(FALLBACK_ICON_THEME)
+}
+
+// FNV-1a, the std hasher isn't guaranteed to give the same hash across Rust versions
+fn get_path_hash(path: &Path) -> u64 {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+// The copy is newer than the icon it was made from as long as the icon wasn't replaced since
+fn is_cache_up_to_date(cached_path: &Path, icon_path: &Path) -> bool {
+    let (cached, icon) = match (fs::metadata(cached_path), fs::metadata(icon_path)) {
+        (Ok(cached), Ok(icon)) => (cached, icon),
+        _ => return false,
+    };
+
+    match (cached.modified(), icon.modified()) {
+        (Ok(cached_modified), Ok(icon_modified)) => {
+            cached.len() == icon.len() && cached_modified >= icon_modified
+        }
+        _ => false,
+    }
+}
+
+fn read_theme(base_dirs: &[PathBuf], theme_name: &str) -> Option<IconTheme> {
+    let dirs: Vec<PathBuf> = base_dirs
+        .iter()
+        .map(|dir| dir.join(theme_name))
+        .filter(|dir| dir.is_dir())
+        .collect();
+
+    // The first index.theme found is the one that describes the theme
+    let index_path = dirs
+        .iter()
+        .map(|dir| dir.join("index.theme"))
+        .find(|path| path.is_file())?;
+
+    let content = fs::read_to_string(&index_path).ok()?;
+    let groups = parse_groups(&content);
+    let theme_group = groups.get("Icon Theme")?;
+
+    let get_list = |key: &str| -> Vec<String> {
+        theme_group
+            .get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_owned())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut directory_names = get_list("Directories");
+    directory_names.extend(get_list("ScaledDirectories"));
+
+    let directories = directory_names
+        .into_iter()
+        .filter_map(|name| {
+            let group = groups.get(&name)?;
+            let get_number = |key: &str| group.get(key).and_then(|value| value.parse::<u32>().ok());
+
+            let size = get_number("Size")?;
+
+            let directory_type = match group.get("Type").map(|value| value.as_str()) {
+                Some("Fixed") => DirectoryType::Fixed,
+                Some("Scalable") => DirectoryType::Scalable,
+                _ => DirectoryType::Threshold,
+            };
+
+            Some(ThemeDirectory {
+                path: name,
+                size,
+                scale: get_number("Scale").unwrap_or(1),
+                directory_type,
+                min_size: get_number("MinSize").unwrap_or(size),
+                max_size: get_number("MaxSize").unwrap_or(size),
+                threshold: get_number("Threshold").unwrap_or(2),
+            })
+        })
+        .collect();
+
+    Some(IconTheme {
+        dirs,
+        inherits: get_list("Inherits"),
+        directories,
+    })
+}
+
+fn parse_groups(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut groups = HashMap::<String, HashMap<String, String>>::new();
+    let mut current: Option<String> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].to_owned();
+            groups.entry(name.to_owned()).or_default();
+            current = Some(name);
+            continue;
+        }
+
+        if let (Some(group), Some((key, value))) = (&current, line.split_once('=')) {
+            groups
+                .entry(group.to_owned())
+                .or_default()
+                .entry(key.trim().to_owned())
+                .or_insert(value.trim().to_owned());
+        }
+    }
+
+    groups
+}
+
+fn find_icon_file(
+    theme_dirs: &[PathBuf],
+    subdir: &str,
+    icon: &str,
+    extensions: &[&str],
+) -> Option<PathBuf> {
+    for dir in theme_dirs {
+        for extension in extensions {
+            let path = dir.join(subdir).join(format!("{}.{}", icon, extension));
+
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+// Removes the cached icons that aren't in `used`, so icons of uninstalled apps or from a
+// previous theme don't pile up
+pub fn try_prune_icon_cache(used: &HashSet<PathBuf>) -> Result<(), WhiskersError> {
+    let entries = match fs::read_dir(get_indexing_icons_dir()) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.is_file() && !used.contains(&path) {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn prune_icon_cache(used: &HashSet<PathBuf>) {
+    try_prune_icon_cache(used).expect("Error pruning icon cache")
+}
+
+// Resolves the Icon key of a desktop entry into a cached icon path
+pub fn get_desktop_entry_icon(
+    resolver: &mut IconResolver,
+    desktop_entry: &DesktopEntry,
+) -> Option<PathBuf> {
+    let icon = desktop_entry.get("Icon").filter(|icon| !icon.is_empty())?;
+    resolver.try_cache(icon).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::set_test_home;
+
+    use super::*;
+
+    fn write_file(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "icon").unwrap();
+    }
+
+    // Custom inherits Parent which inherits Custom back, hicolor is only reached as the fallback
+    // and pixmaps has unthemed icons
+    fn create_theme_tree(root: &Path) -> (PathBuf, PathBuf) {
+        let icons = root.join("icons");
+        let pixmaps = root.join("pixmaps");
+
+        fs::create_dir_all(icons.join("Custom")).unwrap();
+        fs::write(
+            icons.join("Custom/index.theme"),
+            "[Icon Theme]\n\
+             Name=Custom\n\
+             Inherits=Parent\n\
+             Directories=48x48/apps,24x24/apps,scalable/apps\n\
+             ScaledDirectories=48x48@2/apps\n\
+             \n\
+             [48x48/apps]\nSize=48\nType=Fixed\n\
+             [24x24/apps]\nSize=24\nType=Fixed\n\
+             [scalable/apps]\nSize=48\nMinSize=8\nMaxSize=512\nType=Scalable\n\
+             [48x48@2/apps]\nSize=48\nScale=2\nType=Fixed\n",
+        )
+        .unwrap();
+
+        fs::create_dir_all(icons.join("Parent")).unwrap();
+        fs::write(
+            icons.join("Parent/index.theme"),
+            "[Icon Theme]\nInherits=Custom\nDirectories=apps\n[apps]\nSize=48\nType=Fixed\n",
+        )
+        .unwrap();
+
+        fs::create_dir_all(icons.join("hicolor")).unwrap();
+        fs::write(
+            icons.join("hicolor/index.theme"),
+            "[Icon Theme]\nDirectories=48x48/apps\n[48x48/apps]\nSize=48\nType=Threshold\n",
+        )
+        .unwrap();
+
+        for path in [
+            "Custom/48x48/apps/app.png",
+            "Custom/24x24/apps/app.png",
+            "Custom/48x48@2/apps/app.png",
+            "Custom/scalable/apps/app.svg",
+            "Custom/scalable/apps/vector.svg",
+            "Custom/24x24/apps/small.png",
+            "Custom/48x48/apps/legacy.xpm",
+            "Parent/apps/parent.png",
+            "hicolor/48x48/apps/hicolor.png",
+            "hicolor/48x48/apps/legacy.png",
+        ] {
+            write_file(&icons.join(path));
+        }
+
+        write_file(&pixmaps.join("pixmap.xpm"));
+
+        (icons, pixmaps)
+    }
+
+    fn get_resolver(root: &Path) -> IconResolver {
+        let (icons, pixmaps) = create_theme_tree(root);
+        IconResolver::new()
+            .theme("Custom")
+            .base_dirs(vec![icons, pixmaps])
+    }
+
+    #[test]
+    fn exact_size_and_scale_are_preferred() {
+        let home = set_test_home("icons-size");
+        let mut resolver = get_resolver(&home.path);
+        let icons = home.path.join("icons");
+
+        assert_eq!(
+            resolver.resolve("app"),
+            Some(icons.join("Custom/48x48/apps/app.png"))
+        );
+        assert_eq!(
+            resolver.size(24).resolve("app"),
+            Some(icons.join("Custom/24x24/apps/app.png"))
+        );
+        assert_eq!(
+            resolver.size(48).scale(2).resolve("app"),
+            Some(icons.join("Custom/48x48@2/apps/app.png"))
+        );
+        assert_eq!(
+            resolver.scale(1).size(256).resolve("app"),
+            Some(icons.join("Custom/scalable/apps/app.svg"))
+        );
+    }
+
+    #[test]
+    fn closest_size_is_used_without_an_exact_match() {
+        let home = set_test_home("icons-closest");
+        let mut resolver = get_resolver(&home.path);
+        let icons = home.path.join("icons");
+
+        assert_eq!(
+            resolver.resolve("small"),
+            Some(icons.join("Custom/24x24/apps/small.png"))
+        );
+        assert_eq!(
+            resolver.size(16).resolve("vector"),
+            Some(icons.join("Custom/scalable/apps/vector.svg"))
+        );
+    }
+
+    #[test]
+    fn inherited_themes_and_fallbacks_are_searched() {
+        let home = set_test_home("icons-inherits");
+        let mut resolver = get_resolver(&home.path);
+        let icons = home.path.join("icons");
+
+        assert_eq!(
+            resolver.resolve("parent"),
+            Some(icons.join("Parent/apps/parent.png"))
+        );
+        assert_eq!(
+            resolver.resolve("hicolor"),
+            Some(icons.join("hicolor/48x48/apps/hicolor.png"))
+        );
+        assert_eq!(
+            resolver.resolve("pixmap"),
+            Some(home.path.join("pixmaps/pixmap.xpm"))
+        );
+
+        // The inheritance cycle ends
+        assert_eq!(resolver.resolve("missing"), None);
+        assert_eq!(resolver.resolve(""), None);
+    }
+
+    #[test]
+    fn xpm_is_only_used_without_another_format() {
+        let home = set_test_home("icons-xpm");
+        let mut resolver = get_resolver(&home.path);
+
+        assert_eq!(
+            resolver.resolve("legacy"),
+            Some(home.path.join("icons/hicolor/48x48/apps/legacy.png"))
+        );
+    }
+
+    #[test]
+    fn absolute_paths_are_used_when_they_exist() {
+        let home = set_test_home("icons-absolute");
+        let mut resolver = get_resolver(&home.path);
+        let path = home.path.join("pixmaps/pixmap.xpm");
+
+        assert_eq!(resolver.resolve(path.to_string_lossy()), Some(path));
+        assert_eq!(
+            resolver.resolve(home.path.join("missing.png").to_string_lossy()),
+            None
+        );
+    }
+
+    #[test]
+    fn unused_cached_icons_are_pruned() {
+        let home = set_test_home("icons-prune");
+        let mut resolver = get_resolver(&home.path);
+
+        let app = resolver.try_cache("app").unwrap().unwrap();
+        let parent = resolver.try_cache("parent").unwrap().unwrap();
+        assert_ne!(app, parent);
+        assert_eq!(resolver.try_cache("missing").unwrap(), None);
+
+        try_prune_icon_cache(&HashSet::from([app.to_owned()])).unwrap();
+
+        assert!(app.is_file());
+        assert!(!parent.exists());
+    }
+}
@@ -1,11 +1,36 @@
 use serde::{Deserialize, Serialize};
 
+#[cfg(target_os = "linux")]
+use {
+    crate::{
+        api::settings::try_get_settings,
+        error::WhiskersError,
+        paths::{get_data_dirs, get_indexing_apps_path},
+        protocol::encode,
+        storage::{lock_exclusive, write_atomic},
+    },
+    desktop::{get_current_desktops, get_current_locale, DesktopEntry},
+    icons::{get_desktop_entry_icon, try_prune_icon_cache, IconResolver},
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+    },
+    walkdir::WalkDir,
+};
+
+pub mod desktop;
+pub mod icons;
+
+// The path is the file the app is launched from, the desktop entry on Linux. The command is the
+// Exec line without its field codes when the desktop entry has one.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct App {
     pub id: String,
     pub title: String,
     pub icon: Option<String>,
     pub path: String,
+    #[serde(default)]
+    pub command: Option<String>,
 }
 
 impl App {
@@ -15,6 +40,7 @@ impl App {
             title: title.into(),
             icon: None,
             path: path.into(),
+            command: None,
         }
     }
 
@@ -22,4 +48,194 @@ impl App {
         self.icon = Some(icon.into());
         self.to_owned()
     }
-}
\ No newline at end of file
+
+    pub fn command(&mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self.to_owned()
+    }
+}
+
+// The user applications dir comes first so its entries override the system ones with the same id
+#[cfg(target_os = "linux")]
+pub fn get_applications_dirs() -> Vec<PathBuf> {
+    get_data_dirs()
+        .into_iter()
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+// Scans the desktop entries of the applications dirs and writes the visible apps to the apps
+// index
+#[cfg(target_os = "linux")]
+pub fn try_index_apps() -> Result<Vec<App>, WhiskersError> {
+    // Held while caching icons too so a concurrent index doesn't prune icons this one uses
+    let apps_path = get_indexing_apps_path();
+    let _lock = lock_exclusive(&apps_path)?;
+
+    let blacklist = try_get_settings()?.blacklist;
+    let current_desktops = get_current_desktops();
+    let locale = get_current_locale();
+    let mut icon_resolver = IconResolver::new();
+
+    let mut seen_ids = HashSet::<String>::new();
+    let mut apps = Vec::<App>::new();
+
+    for dir in get_applications_dirs() {
+        for entry in WalkDir::new(&dir)
+            .follow_links(true)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+        {
+            let path = entry.path();
+
+            if !entry.file_type().is_file()
+                || path
+                    .extension()
+                    .is_none_or(|extension| extension != "desktop")
+            {
+                continue;
+            }
+
+            // The desktop file id is its path relative to the applications dir with "/" as "-"
+            let id = path
+                .strip_prefix(&dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('/', "-");
+
+            // Hidden entries still take the id so that they hide the system entry they override
+            if !seen_ids.insert(id.to_owned()) {
+                continue;
+            }
+
+            let desktop_entry = match DesktopEntry::parse(path) {
+                Ok(desktop_entry) => desktop_entry,
+                Err(_) => continue,
+            };
+
+            if !desktop_entry.is_application()
+                || !desktop_entry.is_visible(&current_desktops)
+                || !desktop_entry.is_installed()
+            {
+                continue;
+            }
+
+            let title = match desktop_entry.get_localized("Name", locale.as_deref()) {
+                Some(title) => title.to_owned(),
+                None => continue,
+            };
+
+            let command = match desktop_entry.get_command() {
+                Some(command) => command,
+                None => continue,
+            };
+
+            let is_blacklisted = blacklist.iter().any(|blacklisted| {
+                blacklisted == &id
+                    || blacklisted == &command
+                    || Path::new(blacklisted) == desktop_entry.path
+            });
+
+            if is_blacklisted {
+                continue;
+            }
+
+            let mut app =
+                App::new(id, title, desktop_entry.path.to_string_lossy()).command(command);
+
+            if let Some(icon) = get_desktop_entry_icon(&mut icon_resolver, &desktop_entry) {
+                app.icon(icon.to_string_lossy());
+            }
+
+            apps.push(app);
+        }
+    }
+
+    apps.sort_by(|a, b| {
+        a.title
+            .to_lowercase()
+            .cmp(&b.title.to_lowercase())
+            .then(a.id.cmp(&b.id))
+    });
+
+    write_atomic(&apps_path, encode(&apps)?)?;
+
+    let used_icons: HashSet<PathBuf> = apps
+        .iter()
+        .filter_map(|app| app.icon.as_ref().map(PathBuf::from))
+        .collect();
+
+    try_prune_icon_cache(&used_icons)?;
+
+    Ok(apps)
+}
+
+#[cfg(target_os = "linux")]
+pub fn index_apps() -> Vec<App> {
+    try_index_apps().expect("Error indexing apps")
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{env, fs};
+
+    use crate::testing::set_test_home;
+
+    use super::*;
+
+    #[test]
+    fn user_entries_override_system_entries_with_the_same_id() {
+        let home = set_test_home("indexing-apps");
+        let user_dir = home.path.join(".local/share/applications");
+        let system_dir = home.path.join("system/applications");
+
+        env::remove_var("XDG_DATA_HOME");
+        env::set_var("XDG_DATA_DIRS", home.path.join("system"));
+
+        let entries = [
+            (
+                &user_dir,
+                "editor.desktop",
+                "Name=My Editor\nExec=editor --mine %F",
+            ),
+            (&system_dir, "editor.desktop", "Name=Editor\nExec=editor %F"),
+            (&user_dir, "player.desktop", "Name=Player\nHidden=true"),
+            (&system_dir, "player.desktop", "Name=Player\nExec=player %U"),
+            (
+                &system_dir,
+                "kde/konsole.desktop",
+                "Name=Konsole\nExec=konsole",
+            ),
+            (&system_dir, "no-exec.desktop", "Name=No Exec"),
+        ];
+
+        for (dir, name, content) in entries {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(
+                path,
+                format!("[Desktop Entry]\nType=Application\n{}\n", content),
+            )
+            .unwrap();
+        }
+
+        fs::write(
+            system_dir.join("link.desktop"),
+            "[Desktop Entry]\nType=Link\nName=Link\nExec=link\n",
+        )
+        .unwrap();
+
+        let result = try_index_apps();
+        env::remove_var("XDG_DATA_DIRS");
+
+        let apps = result.unwrap();
+        let ids: Vec<&str> = apps.iter().map(|app| app.id.as_str()).collect();
+        assert_eq!(ids, ["kde-konsole.desktop", "editor.desktop"]);
+
+        let editor = &apps[1];
+        assert_eq!(editor.title, "My Editor");
+        assert_eq!(editor.command.as_deref(), Some("editor --mine"));
+        assert_eq!(Path::new(&editor.path), user_dir.join("editor.desktop"));
+    }
+}
@@ -8,4 +8,15 @@ pub mod api;
 pub mod indexing;
 pub mod result;
 pub mod action;
-pub mod extension;
\ No newline at end of file
+pub mod extension;
+pub mod error;
+pub mod storage;
+pub mod protocol;
+pub mod ranking;
+pub mod routing;
+#[cfg(feature = "suggestions")]
+pub mod suggestions;
+pub mod shortcut;
+pub mod theme;
+#[cfg(test)]
+mod testing;
@@ -113,6 +113,66 @@ pub fn get_dialog_request_path() -> PathBuf {
     path
 }
 
+pub fn get_sessions_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("sessions");
+    path
+}
+
+pub fn get_session_dir(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_sessions_dir();
+    path.push(session_id.into());
+    path
+}
+
+pub fn get_session_extension_request_path(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_session_dir(session_id);
+    path.push("extension-request.bin");
+    path
+}
+
+pub fn get_session_extension_response_path(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_session_dir(session_id);
+    path.push("extension-response.bin");
+    path
+}
+
+pub fn get_session_dialog_request_path(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_session_dir(session_id);
+    path.push("dialog-request.bin");
+    path
+}
+
+pub fn get_session_dialog_response_path(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_session_dir(session_id);
+    path.push("dialog-response.bin");
+    path
+}
+
+pub fn get_extension_request_json_path() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("extension-request.json");
+    path
+}
+
+pub fn get_extension_response_json_path() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("extension-response.json");
+    path
+}
+
+pub fn get_session_extension_request_json_path(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_session_dir(session_id);
+    path.push("extension-request.json");
+    path
+}
+
+pub fn get_session_extension_response_json_path(session_id: impl Into<String>) -> PathBuf {
+    let mut path = get_session_dir(session_id);
+    path.push("extension-response.json");
+    path
+}
+
 pub fn get_extensions_dir() -> PathBuf {
     let mut path = get_app_dir();
     path.push("extensions");
@@ -146,11 +206,37 @@ pub fn get_autostart_dir() -> PathBuf {
         _ => {
             let mut path = get_home_dir();
             path.push(".config/autostart");
-            return path;
+            path
         }
     }
 }
 
+// XDG_DATA_HOME followed by XDG_DATA_DIRS, with the spec defaults when they aren't set
+pub fn get_data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or(get_home_dir().join(".local/share"));
+
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or(String::from("/usr/local/share:/usr/share"));
+
+    let mut dirs = vec![data_home];
+
+    for dir in data_dirs.split(':').filter(|dir| !dir.is_empty()) {
+        let dir = PathBuf::from(dir);
+
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+
+    dirs
+}
+
 pub fn get_stores_dir() -> PathBuf {
     let mut path = get_app_dir();
     path.push("stores");
@@ -174,3 +260,9 @@ pub fn get_recent_apps_path() -> PathBuf{
     path.push("recent-apps.bin");
     path
 }
+
+pub fn get_frecency_path() -> PathBuf {
+    let mut path = get_app_dir();
+    path.push("frecency.bin");
+    path
+}
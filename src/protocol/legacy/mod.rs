@@ -0,0 +1,936 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{
+        Action, ActionType, CopyAction, DialogAction, ExtensionAction, FieldType, OpenAppAction,
+        OpenURLAction,
+    },
+    api::extensions::{
+        ActionContext, DialogResponse, DialogResult, ExtensionRequest, ExtensionResponse,
+    },
+    error::{decode_error, encode_error, WhiskersError},
+    extension::{
+        Extension, ExtensionMode, ExtensionSetting, SelectOption, SettingType, ShowCondition,
+    },
+    indexing::App,
+    result::{HighlightRange, ResultType, TextResult, TitleAndDescriptionResult, WLResult},
+    settings::{self, SearchEngine, SettingValue, Settings, Theme},
+};
+
+use super::{Versioned, WireFormat, LEGACY_PROTOCOL_VERSION};
+
+// =================================================================
+// Version 0 (crate 2.0, no header)
+// =================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionRequestV0 {
+    pub extension_id: String,
+    pub action_context: ActionContext,
+    pub extension_action: Option<String>,
+    pub search_text: Option<String>,
+    pub args: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DialogResponseV0 {
+    pub results: Vec<DialogResultV0>,
+    pub args: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DialogResultV0 {
+    pub field_id: String,
+    pub field_value: String,
+    pub args: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionV0 {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub keyword: String,
+    pub settings: Option<Vec<ManifestSettingV1>>,
+    pub os: String,
+}
+
+impl From<ExtensionRequestV0> for ExtensionRequest {
+    fn from(request: ExtensionRequestV0) -> Self {
+        Self {
+            extension_id: request.extension_id,
+            action_context: request.action_context,
+            extension_action: request.extension_action,
+            search_text: request.search_text,
+            args: request.args,
+            session_id: None,
+        }
+    }
+}
+
+impl From<ExtensionRequest> for ExtensionRequestV0 {
+    fn from(request: ExtensionRequest) -> Self {
+        Self {
+            extension_id: request.extension_id,
+            action_context: request.action_context,
+            extension_action: request.extension_action,
+            search_text: request.search_text,
+            args: request.args,
+        }
+    }
+}
+
+// Version 0 didn't send field types so every value is treated as plain text
+impl From<DialogResponseV0> for DialogResponse {
+    fn from(response: DialogResponseV0) -> Self {
+        Self {
+            results: response
+                .results
+                .into_iter()
+                .map(|result| DialogResult {
+                    field_id: result.field_id,
+                    field_type: FieldType::Input,
+                    field_value: result.field_value,
+                    args: result.args,
+                })
+                .collect(),
+            args: response.args,
+        }
+    }
+}
+
+impl From<DialogResponse> for DialogResponseV0 {
+    fn from(response: DialogResponse) -> Self {
+        Self {
+            results: response
+                .results
+                .into_iter()
+                .map(|result| DialogResultV0 {
+                    field_id: result.field_id,
+                    field_value: result.field_value,
+                    args: result.args,
+                })
+                .collect(),
+            args: response.args,
+        }
+    }
+}
+
+impl From<ExtensionV0> for Extension {
+    fn from(extension: ExtensionV0) -> Self {
+        Self {
+            id: extension.id,
+            name: extension.name,
+            description: extension.description,
+            keyword: extension.keyword,
+            settings: from_manifest_settings_v1(extension.settings),
+            os: extension.os,
+            mode: ExtensionMode::OneShot,
+            api_version: LEGACY_PROTOCOL_VERSION,
+            protocol: WireFormat::Bincode,
+        }
+    }
+}
+
+impl From<Extension> for ExtensionV0 {
+    fn from(extension: Extension) -> Self {
+        Self {
+            id: extension.id,
+            name: extension.name,
+            description: extension.description,
+            keyword: extension.keyword,
+            settings: to_manifest_settings_v1(extension.settings),
+            os: extension.os,
+        }
+    }
+}
+
+// =================================================================
+// Version 1 (shapes also used by version 0)
+// =================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestSettingV1 {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub setting_type: SettingType,
+    pub default_value: String,
+    pub show_conditions: Option<Vec<ShowCondition>>,
+    pub select_options: Option<Vec<SelectOption>>,
+    pub os: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionV1 {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub keyword: String,
+    pub settings: Option<Vec<ManifestSettingV1>>,
+    pub os: String,
+    pub mode: ExtensionMode,
+    pub api_version: u16,
+    pub protocol: WireFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionSettingV1 {
+    pub extension_id: String,
+    pub setting_id: String,
+    pub setting_value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SettingsV1 {
+    pub first_key: String,
+    pub second_key: Option<String>,
+    pub third_key: String,
+    pub scaling: f32,
+    pub auto_start: bool,
+    pub show_recent_apps: bool,
+    pub split_results: bool,
+    pub show_search_icon: bool,
+    pub show_settings_icon: bool,
+    pub show_placeholder: bool,
+    pub accent_search_border: bool,
+    pub hide_on_blur: bool,
+    pub border_radius: usize,
+    pub border_width: usize,
+    pub highlight_selected_background: bool,
+    pub show_alt_hint: bool,
+    pub results_count: usize,
+    pub blacklist: Vec<String>,
+    pub search_keyword: String,
+    pub search_engines: Vec<SearchEngineV3>,
+    pub default_search_engine: usize,
+    pub theme: Theme,
+    pub extensions: Vec<ExtensionSettingV1>,
+}
+
+// Setting values were plain strings, they get their real type once the extensions are indexed
+fn from_manifest_settings_v1(
+    settings: Option<Vec<ManifestSettingV1>>,
+) -> Option<Vec<ExtensionSetting>> {
+    settings.map(|settings| {
+        settings
+            .into_iter()
+            .map(|setting| ExtensionSetting {
+                default_value: SettingValue::Text(setting.default_value)
+                    .coerce(&setting.setting_type),
+                id: setting.id,
+                title: setting.title,
+                description: setting.description,
+                setting_type: setting.setting_type,
+                show_conditions: setting.show_conditions,
+                select_options: setting.select_options,
+                os: setting.os,
+            })
+            .collect()
+    })
+}
+
+fn to_manifest_settings_v1(
+    settings: Option<Vec<ExtensionSetting>>,
+) -> Option<Vec<ManifestSettingV1>> {
+    settings.map(|settings| {
+        settings
+            .into_iter()
+            .map(|setting| ManifestSettingV1 {
+                id: setting.id,
+                title: setting.title,
+                description: setting.description,
+                setting_type: setting.setting_type,
+                default_value: setting.default_value.to_string(),
+                show_conditions: setting.show_conditions,
+                select_options: setting.select_options,
+                os: setting.os,
+            })
+            .collect()
+    })
+}
+
+impl From<ExtensionV1> for Extension {
+    fn from(extension: ExtensionV1) -> Self {
+        Self {
+            id: extension.id,
+            name: extension.name,
+            description: extension.description,
+            keyword: extension.keyword,
+            settings: from_manifest_settings_v1(extension.settings),
+            os: extension.os,
+            mode: extension.mode,
+            api_version: extension.api_version,
+            protocol: extension.protocol,
+        }
+    }
+}
+
+impl From<Extension> for ExtensionV1 {
+    fn from(extension: Extension) -> Self {
+        Self {
+            id: extension.id,
+            name: extension.name,
+            description: extension.description,
+            keyword: extension.keyword,
+            settings: to_manifest_settings_v1(extension.settings),
+            os: extension.os,
+            mode: extension.mode,
+            api_version: extension.api_version,
+            protocol: extension.protocol,
+        }
+    }
+}
+
+impl From<SettingsV1> for Settings {
+    fn from(settings: SettingsV1) -> Self {
+        Self {
+            first_key: settings.first_key,
+            second_key: settings.second_key,
+            third_key: settings.third_key,
+            scaling: settings.scaling,
+            auto_start: settings.auto_start,
+            show_recent_apps: settings.show_recent_apps,
+            split_results: settings.split_results,
+            show_search_icon: settings.show_search_icon,
+            show_settings_icon: settings.show_settings_icon,
+            show_placeholder: settings.show_placeholder,
+            accent_search_border: settings.accent_search_border,
+            hide_on_blur: settings.hide_on_blur,
+            border_radius: settings.border_radius,
+            border_width: settings.border_width,
+            highlight_selected_background: settings.highlight_selected_background,
+            show_alt_hint: settings.show_alt_hint,
+            results_count: settings.results_count,
+            blacklist: settings.blacklist,
+            search_keyword: settings.search_keyword,
+            search_engines: from_search_engines_v3(settings.search_engines),
+            default_search_engine: settings.default_search_engine,
+            theme: settings.theme,
+            extensions: settings
+                .extensions
+                .into_iter()
+                .map(|setting| settings::ExtensionSetting {
+                    extension_id: setting.extension_id,
+                    setting_id: setting.setting_id,
+                    setting_value: SettingValue::Text(setting.setting_value),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Settings> for SettingsV1 {
+    fn from(settings: Settings) -> Self {
+        Self {
+            first_key: settings.first_key,
+            second_key: settings.second_key,
+            third_key: settings.third_key,
+            scaling: settings.scaling,
+            auto_start: settings.auto_start,
+            show_recent_apps: settings.show_recent_apps,
+            split_results: settings.split_results,
+            show_search_icon: settings.show_search_icon,
+            show_settings_icon: settings.show_settings_icon,
+            show_placeholder: settings.show_placeholder,
+            accent_search_border: settings.accent_search_border,
+            hide_on_blur: settings.hide_on_blur,
+            border_radius: settings.border_radius,
+            border_width: settings.border_width,
+            highlight_selected_background: settings.highlight_selected_background,
+            show_alt_hint: settings.show_alt_hint,
+            results_count: settings.results_count,
+            blacklist: settings.blacklist,
+            search_keyword: settings.search_keyword,
+            search_engines: to_search_engines_v3(settings.search_engines),
+            default_search_engine: settings.default_search_engine,
+            theme: settings.theme,
+            extensions: settings
+                .extensions
+                .into_iter()
+                .map(|setting| ExtensionSettingV1 {
+                    extension_id: setting.extension_id,
+                    setting_id: setting.setting_id,
+                    setting_value: setting.setting_value.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// =================================================================
+// Version 2 (shapes also used by versions 0 and 1)
+// =================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionResponseV2 {
+    pub results: Vec<WLResultV2>,
+    pub args: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WLResultV2 {
+    pub result_type: ResultType,
+    pub text: Option<TextResultV2>,
+    pub title_and_description: Option<TitleAndDescriptionResultV2>,
+    pub divider: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TextResultV2 {
+    pub icon: Option<String>,
+    pub tint: Option<String>,
+    pub text: String,
+    pub action: ActionV4,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TitleAndDescriptionResultV2 {
+    pub icon: Option<String>,
+    pub tint: Option<String>,
+    pub title: String,
+    pub description: String,
+    pub action: ActionV4,
+}
+
+// Highlights didn't exist so old results come without them and lose them when downgraded
+impl From<WLResultV2> for WLResult {
+    fn from(result: WLResultV2) -> Self {
+        Self {
+            result_type: result.result_type,
+            text: result.text.map(|text| TextResult {
+                icon: text.icon,
+                tint: text.tint,
+                text: text.text,
+                action: text.action.into(),
+                highlights: None,
+            }),
+            title_and_description: result.title_and_description.map(|result| {
+                TitleAndDescriptionResult {
+                    icon: result.icon,
+                    tint: result.tint,
+                    title: result.title,
+                    description: result.description,
+                    action: result.action.into(),
+                    highlights: None,
+                }
+            }),
+            divider: result.divider,
+        }
+    }
+}
+
+impl From<WLResult> for WLResultV2 {
+    fn from(result: WLResult) -> Self {
+        Self {
+            result_type: result.result_type,
+            text: result.text.map(|text| TextResultV2 {
+                icon: text.icon,
+                tint: text.tint,
+                text: text.text,
+                action: text.action.into(),
+            }),
+            title_and_description: result.title_and_description.map(|result| {
+                TitleAndDescriptionResultV2 {
+                    icon: result.icon,
+                    tint: result.tint,
+                    title: result.title,
+                    description: result.description,
+                    action: result.action.into(),
+                }
+            }),
+            divider: result.divider,
+        }
+    }
+}
+
+impl From<ExtensionResponseV2> for ExtensionResponse {
+    fn from(response: ExtensionResponseV2) -> Self {
+        Self {
+            results: response.results.into_iter().map(WLResult::from).collect(),
+            args: response.args,
+        }
+    }
+}
+
+impl From<ExtensionResponse> for ExtensionResponseV2 {
+    fn from(response: ExtensionResponse) -> Self {
+        Self {
+            results: response.results.into_iter().map(WLResultV2::from).collect(),
+            args: response.args,
+        }
+    }
+}
+
+// =================================================================
+// Version 3 (shapes also used by versions 0 to 2)
+// =================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchEngineV3 {
+    pub id: usize,
+    pub icon_path: Option<String>,
+    pub tint_icon: bool,
+    pub keyword: String,
+    pub name: String,
+    pub search_query: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SettingsV3 {
+    pub first_key: String,
+    pub second_key: Option<String>,
+    pub third_key: String,
+    pub scaling: f32,
+    pub auto_start: bool,
+    pub show_recent_apps: bool,
+    pub split_results: bool,
+    pub show_search_icon: bool,
+    pub show_settings_icon: bool,
+    pub show_placeholder: bool,
+    pub accent_search_border: bool,
+    pub hide_on_blur: bool,
+    pub border_radius: usize,
+    pub border_width: usize,
+    pub highlight_selected_background: bool,
+    pub show_alt_hint: bool,
+    pub results_count: usize,
+    pub blacklist: Vec<String>,
+    pub search_keyword: String,
+    pub search_engines: Vec<SearchEngineV3>,
+    pub default_search_engine: usize,
+    pub theme: Theme,
+    pub extensions: Vec<settings::ExtensionSetting>,
+}
+
+// Suggestions send the query to the engine as it's typed so they stay off until the user sets them
+fn from_search_engines_v3(search_engines: Vec<SearchEngineV3>) -> Vec<SearchEngine> {
+    search_engines
+        .into_iter()
+        .map(|engine| SearchEngine {
+            suggestions_query: None,
+            id: engine.id,
+            icon_path: engine.icon_path,
+            tint_icon: engine.tint_icon,
+            keyword: engine.keyword,
+            name: engine.name,
+            search_query: engine.search_query,
+        })
+        .collect()
+}
+
+fn to_search_engines_v3(search_engines: Vec<SearchEngine>) -> Vec<SearchEngineV3> {
+    search_engines
+        .into_iter()
+        .map(|engine| SearchEngineV3 {
+            id: engine.id,
+            icon_path: engine.icon_path,
+            tint_icon: engine.tint_icon,
+            keyword: engine.keyword,
+            name: engine.name,
+            search_query: engine.search_query,
+        })
+        .collect()
+}
+
+impl From<SettingsV3> for Settings {
+    fn from(settings: SettingsV3) -> Self {
+        Self {
+            first_key: settings.first_key,
+            second_key: settings.second_key,
+            third_key: settings.third_key,
+            scaling: settings.scaling,
+            auto_start: settings.auto_start,
+            show_recent_apps: settings.show_recent_apps,
+            split_results: settings.split_results,
+            show_search_icon: settings.show_search_icon,
+            show_settings_icon: settings.show_settings_icon,
+            show_placeholder: settings.show_placeholder,
+            accent_search_border: settings.accent_search_border,
+            hide_on_blur: settings.hide_on_blur,
+            border_radius: settings.border_radius,
+            border_width: settings.border_width,
+            highlight_selected_background: settings.highlight_selected_background,
+            show_alt_hint: settings.show_alt_hint,
+            results_count: settings.results_count,
+            blacklist: settings.blacklist,
+            search_keyword: settings.search_keyword,
+            search_engines: from_search_engines_v3(settings.search_engines),
+            default_search_engine: settings.default_search_engine,
+            theme: settings.theme,
+            extensions: settings.extensions,
+        }
+    }
+}
+
+impl From<Settings> for SettingsV3 {
+    fn from(settings: Settings) -> Self {
+        Self {
+            first_key: settings.first_key,
+            second_key: settings.second_key,
+            third_key: settings.third_key,
+            scaling: settings.scaling,
+            auto_start: settings.auto_start,
+            show_recent_apps: settings.show_recent_apps,
+            split_results: settings.split_results,
+            show_search_icon: settings.show_search_icon,
+            show_settings_icon: settings.show_settings_icon,
+            show_placeholder: settings.show_placeholder,
+            accent_search_border: settings.accent_search_border,
+            hide_on_blur: settings.hide_on_blur,
+            border_radius: settings.border_radius,
+            border_width: settings.border_width,
+            highlight_selected_background: settings.highlight_selected_background,
+            show_alt_hint: settings.show_alt_hint,
+            results_count: settings.results_count,
+            blacklist: settings.blacklist,
+            search_keyword: settings.search_keyword,
+            search_engines: to_search_engines_v3(settings.search_engines),
+            default_search_engine: settings.default_search_engine,
+            theme: settings.theme,
+            extensions: settings.extensions,
+        }
+    }
+}
+
+// =================================================================
+// Version 4 (shapes also used by versions 0 to 3)
+// =================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActionV4 {
+    pub action_type: ActionType,
+    pub open_app: Option<OpenAppAction>,
+    pub open_url: Option<OpenURLAction>,
+    pub copy: Option<CopyAction>,
+    pub extension: Option<ExtensionAction>,
+    pub dialog: Option<DialogAction>,
+    pub ignore: bool,
+    pub ask_confirmation: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionResponseV4 {
+    pub results: Vec<WLResultV4>,
+    pub args: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WLResultV4 {
+    pub result_type: ResultType,
+    pub text: Option<TextResultV4>,
+    pub title_and_description: Option<TitleAndDescriptionResultV4>,
+    pub divider: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TextResultV4 {
+    pub icon: Option<String>,
+    pub tint: Option<String>,
+    pub text: String,
+    pub action: ActionV4,
+    pub highlights: Option<Vec<HighlightRange>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TitleAndDescriptionResultV4 {
+    pub icon: Option<String>,
+    pub tint: Option<String>,
+    pub title: String,
+    pub description: String,
+    pub action: ActionV4,
+    pub highlights: Option<Vec<HighlightRange>>,
+}
+
+impl From<ActionV4> for Action {
+    fn from(action: ActionV4) -> Self {
+        Self {
+            action_type: action.action_type,
+            open_app: action.open_app,
+            open_url: action.open_url,
+            copy: action.copy,
+            extension: action.extension,
+            dialog: action.dialog,
+            run_command: None,
+            ignore: action.ignore,
+            ask_confirmation: action.ask_confirmation,
+        }
+    }
+}
+
+// Older launchers can't run commands so the action is ignored there
+impl From<Action> for ActionV4 {
+    fn from(action: Action) -> Self {
+        if let ActionType::RunCommand = action.action_type {
+            return Self {
+                action_type: ActionType::Ignore,
+                open_app: None,
+                open_url: None,
+                copy: None,
+                extension: None,
+                dialog: None,
+                ignore: true,
+                ask_confirmation: false,
+            };
+        }
+
+        Self {
+            action_type: action.action_type,
+            open_app: action.open_app,
+            open_url: action.open_url,
+            copy: action.copy,
+            extension: action.extension,
+            dialog: action.dialog,
+            ignore: action.ignore,
+            ask_confirmation: action.ask_confirmation,
+        }
+    }
+}
+
+impl From<WLResultV4> for WLResult {
+    fn from(result: WLResultV4) -> Self {
+        Self {
+            result_type: result.result_type,
+            text: result.text.map(|text| TextResult {
+                icon: text.icon,
+                tint: text.tint,
+                text: text.text,
+                action: text.action.into(),
+                highlights: text.highlights,
+            }),
+            title_and_description: result.title_and_description.map(|result| {
+                TitleAndDescriptionResult {
+                    icon: result.icon,
+                    tint: result.tint,
+                    title: result.title,
+                    description: result.description,
+                    action: result.action.into(),
+                    highlights: result.highlights,
+                }
+            }),
+            divider: result.divider,
+        }
+    }
+}
+
+impl From<WLResult> for WLResultV4 {
+    fn from(result: WLResult) -> Self {
+        Self {
+            result_type: result.result_type,
+            text: result.text.map(|text| TextResultV4 {
+                icon: text.icon,
+                tint: text.tint,
+                text: text.text,
+                action: text.action.into(),
+                highlights: text.highlights,
+            }),
+            title_and_description: result.title_and_description.map(|result| {
+                TitleAndDescriptionResultV4 {
+                    icon: result.icon,
+                    tint: result.tint,
+                    title: result.title,
+                    description: result.description,
+                    action: result.action.into(),
+                    highlights: result.highlights,
+                }
+            }),
+            divider: result.divider,
+        }
+    }
+}
+
+impl From<ExtensionResponseV4> for ExtensionResponse {
+    fn from(response: ExtensionResponseV4) -> Self {
+        Self {
+            results: response.results.into_iter().map(WLResult::from).collect(),
+            args: response.args,
+        }
+    }
+}
+
+impl From<ExtensionResponse> for ExtensionResponseV4 {
+    fn from(response: ExtensionResponse) -> Self {
+        Self {
+            results: response.results.into_iter().map(WLResultV4::from).collect(),
+            args: response.args,
+        }
+    }
+}
+
+// =================================================================
+// Version 5 (shapes also used by versions 0 to 4)
+// =================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AppV5 {
+    pub id: String,
+    pub title: String,
+    pub icon: Option<String>,
+    pub path: String,
+}
+
+impl From<AppV5> for App {
+    fn from(app: AppV5) -> Self {
+        Self {
+            id: app.id,
+            title: app.title,
+            icon: app.icon,
+            path: app.path,
+            command: None,
+        }
+    }
+}
+
+impl From<App> for AppV5 {
+    fn from(app: App) -> Self {
+        Self {
+            id: app.id,
+            title: app.title,
+            icon: app.icon,
+            path: app.path,
+        }
+    }
+}
+
+// =================================================================
+// Versioned implementations
+// =================================================================
+
+impl Versioned for ExtensionRequest {
+    fn decode_legacy(version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        match version {
+            LEGACY_PROTOCOL_VERSION => bincode::deserialize::<ExtensionRequestV0>(payload)
+                .map(ExtensionRequest::from)
+                .map_err(decode_error),
+            _ => bincode::deserialize(payload).map_err(decode_error),
+        }
+    }
+
+    fn encode_legacy(&self, version: u16) -> Result<Vec<u8>, WhiskersError> {
+        match version {
+            LEGACY_PROTOCOL_VERSION => {
+                bincode::serialize(&ExtensionRequestV0::from(self.to_owned())).map_err(encode_error)
+            }
+            _ => bincode::serialize(self).map_err(encode_error),
+        }
+    }
+}
+
+impl Versioned for DialogResponse {
+    fn decode_legacy(version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        match version {
+            LEGACY_PROTOCOL_VERSION => bincode::deserialize::<DialogResponseV0>(payload)
+                .map(DialogResponse::from)
+                .map_err(decode_error),
+            _ => bincode::deserialize(payload).map_err(decode_error),
+        }
+    }
+
+    fn encode_legacy(&self, version: u16) -> Result<Vec<u8>, WhiskersError> {
+        match version {
+            LEGACY_PROTOCOL_VERSION => {
+                bincode::serialize(&DialogResponseV0::from(self.to_owned())).map_err(encode_error)
+            }
+            _ => bincode::serialize(self).map_err(encode_error),
+        }
+    }
+}
+
+impl Versioned for Vec<Extension> {
+    fn decode_legacy(version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        match version {
+            LEGACY_PROTOCOL_VERSION => bincode::deserialize::<Vec<ExtensionV0>>(payload)
+                .map(|extensions| extensions.into_iter().map(Extension::from).collect())
+                .map_err(decode_error),
+            1 => bincode::deserialize::<Vec<ExtensionV1>>(payload)
+                .map(|extensions| extensions.into_iter().map(Extension::from).collect())
+                .map_err(decode_error),
+            _ => bincode::deserialize(payload).map_err(decode_error),
+        }
+    }
+
+    fn encode_legacy(&self, version: u16) -> Result<Vec<u8>, WhiskersError> {
+        match version {
+            LEGACY_PROTOCOL_VERSION => {
+                let extensions: Vec<ExtensionV0> =
+                    self.iter().cloned().map(ExtensionV0::from).collect();
+
+                bincode::serialize(&extensions).map_err(encode_error)
+            }
+            1 => {
+                let extensions: Vec<ExtensionV1> =
+                    self.iter().cloned().map(ExtensionV1::from).collect();
+
+                bincode::serialize(&extensions).map_err(encode_error)
+            }
+            _ => bincode::serialize(self).map_err(encode_error),
+        }
+    }
+}
+
+impl Versioned for Settings {
+    fn decode_legacy(version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        match version {
+            0 | 1 => bincode::deserialize::<SettingsV1>(payload)
+                .map(Settings::from)
+                .map_err(decode_error),
+            2 | 3 => bincode::deserialize::<SettingsV3>(payload)
+                .map(Settings::from)
+                .map_err(decode_error),
+            _ => bincode::deserialize(payload).map_err(decode_error),
+        }
+    }
+
+    fn encode_legacy(&self, version: u16) -> Result<Vec<u8>, WhiskersError> {
+        match version {
+            0 | 1 => bincode::serialize(&SettingsV1::from(self.to_owned())).map_err(encode_error),
+            2 | 3 => bincode::serialize(&SettingsV3::from(self.to_owned())).map_err(encode_error),
+            _ => bincode::serialize(self).map_err(encode_error),
+        }
+    }
+}
+
+impl Versioned for ExtensionResponse {
+    fn decode_legacy(version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        match version {
+            0..=2 => bincode::deserialize::<ExtensionResponseV2>(payload)
+                .map(ExtensionResponse::from)
+                .map_err(decode_error),
+            3 | 4 => bincode::deserialize::<ExtensionResponseV4>(payload)
+                .map(ExtensionResponse::from)
+                .map_err(decode_error),
+            _ => bincode::deserialize(payload).map_err(decode_error),
+        }
+    }
+
+    fn encode_legacy(&self, version: u16) -> Result<Vec<u8>, WhiskersError> {
+        match version {
+            0..=2 => bincode::serialize(&ExtensionResponseV2::from(self.to_owned()))
+                .map_err(encode_error),
+            3 | 4 => bincode::serialize(&ExtensionResponseV4::from(self.to_owned()))
+                .map_err(encode_error),
+            _ => bincode::serialize(self).map_err(encode_error),
+        }
+    }
+}
+
+impl Versioned for Vec<App> {
+    fn decode_legacy(version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        match version {
+            0..=5 => bincode::deserialize::<Vec<AppV5>>(payload)
+                .map(|apps| apps.into_iter().map(App::from).collect())
+                .map_err(decode_error),
+            _ => bincode::deserialize(payload).map_err(decode_error),
+        }
+    }
+
+    fn encode_legacy(&self, version: u16) -> Result<Vec<u8>, WhiskersError> {
+        match version {
+            0..=5 => {
+                let apps: Vec<AppV5> = self.iter().cloned().map(AppV5::from).collect();
+                bincode::serialize(&apps).map_err(encode_error)
+            }
+            _ => bincode::serialize(self).map_err(encode_error),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::env;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    action::DialogAction,
+    error::{decode_error, encode_error, WhiskersError},
+};
+
+pub mod legacy;
+
+pub const PROTOCOL_MAGIC: [u8; 4] = *b"WLPV";
+
+// Bump whenever the encoded shape of a versioned type changes and teach `decode_legacy` how to
+// read the previous shape
+pub const PROTOCOL_VERSION: u16 = 6;
+
+// Files written before the header existed (crate 2.0) are treated as version 0
+pub const LEGACY_PROTOCOL_VERSION: u16 = 0;
+
+const HEADER_LENGTH: usize = PROTOCOL_MAGIC.len() + 2;
+
+pub const WIRE_FORMAT_ENV: &str = "WHISKERS_WIRE_FORMAT";
+
+// Bincode is versioned with the protocol header while JSON relies on its field names, which keeps
+// it easy to produce from other languages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    Bincode,
+    Json,
+}
+
+impl WireFormat {
+    pub fn from_env() -> Self {
+        match env::var(WIRE_FORMAT_ENV) {
+            Ok(format) if format == "json" => WireFormat::Json,
+            _ => WireFormat::Bincode,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WireFormat::Bincode => "bincode",
+            WireFormat::Json => "json",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            WireFormat::Bincode => "bin",
+            WireFormat::Json => "json",
+        }
+    }
+
+    pub fn encode<T: Versioned>(&self, value: &T) -> Result<Vec<u8>, WhiskersError> {
+        match self {
+            WireFormat::Bincode => encode(value),
+            WireFormat::Json => serde_json::to_vec(value).map_err(encode_error),
+        }
+    }
+
+    pub fn encode_version<T: Versioned>(
+        &self,
+        value: &T,
+        version: u16,
+    ) -> Result<Vec<u8>, WhiskersError> {
+        match self {
+            WireFormat::Bincode => encode_version(value, version),
+            WireFormat::Json => self.encode(value),
+        }
+    }
+
+    // JSON has no header so it's always read as the current version
+    pub fn get_version(&self, bytes: &[u8]) -> u16 {
+        match self {
+            WireFormat::Bincode => get_version(bytes),
+            WireFormat::Json => PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn decode<T: Versioned>(&self, bytes: &[u8]) -> Result<T, WhiskersError> {
+        match self {
+            WireFormat::Bincode => decode(bytes),
+            WireFormat::Json => serde_json::from_slice(bytes).map_err(decode_error),
+        }
+    }
+}
+
+pub trait Versioned: Serialize + DeserializeOwned {
+    fn decode_legacy(_version: u16, payload: &[u8]) -> Result<Self, WhiskersError> {
+        bincode::deserialize(payload).map_err(decode_error)
+    }
+
+    fn encode_legacy(&self, _version: u16) -> Result<Vec<u8>, WhiskersError> {
+        bincode::serialize(self).map_err(encode_error)
+    }
+}
+
+impl Versioned for DialogAction {}
+
+pub fn encode<T: Versioned>(value: &T) -> Result<Vec<u8>, WhiskersError> {
+    encode_version(value, PROTOCOL_VERSION)
+}
+
+pub fn encode_version<T: Versioned>(value: &T, version: u16) -> Result<Vec<u8>, WhiskersError> {
+    if version > PROTOCOL_VERSION {
+        return Err(WhiskersError::UnsupportedVersion {
+            version,
+            supported: PROTOCOL_VERSION,
+        });
+    }
+
+    let payload = if version == PROTOCOL_VERSION {
+        bincode::serialize(value).map_err(encode_error)?
+    } else {
+        value.encode_legacy(version)?
+    };
+
+    if version == LEGACY_PROTOCOL_VERSION {
+        return Ok(payload);
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    bytes.extend_from_slice(&PROTOCOL_MAGIC);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+
+    Ok(bytes)
+}
+
+pub fn decode<T: Versioned>(bytes: &[u8]) -> Result<T, WhiskersError> {
+    let version = get_version(bytes);
+
+    if version > PROTOCOL_VERSION {
+        return Err(WhiskersError::UnsupportedVersion {
+            version,
+            supported: PROTOCOL_VERSION,
+        });
+    }
+
+    if version == LEGACY_PROTOCOL_VERSION {
+        return T::decode_legacy(version, bytes);
+    }
+
+    let payload = &bytes[HEADER_LENGTH..];
+
+    if version == PROTOCOL_VERSION {
+        bincode::deserialize(payload).map_err(decode_error)
+    } else {
+        T::decode_legacy(version, payload)
+    }
+}
+
+pub fn get_version(bytes: &[u8]) -> u16 {
+    if bytes.len() >= HEADER_LENGTH && bytes[..PROTOCOL_MAGIC.len()] == PROTOCOL_MAGIC {
+        u16::from_le_bytes([bytes[4], bytes[5]])
+    } else {
+        LEGACY_PROTOCOL_VERSION
+    }
+}
+
+// Picks the version both sides understand when talking to an extension built against
+// `api_version`
+pub fn negotiate_version(api_version: u16) -> Result<u16, WhiskersError> {
+    if api_version > PROTOCOL_VERSION {
+        return Err(WhiskersError::UnsupportedVersion {
+            version: api_version,
+            supported: PROTOCOL_VERSION,
+        });
+    }
+
+    Ok(api_version)
+}
+
+pub fn is_supported_version(version: u16) -> bool {
+    version <= PROTOCOL_VERSION
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{
+        action::{
+            Action, CopyAction, DialogAction, ExtensionAction, Field, InputField, OpenURLAction,
+        },
+        api::extensions::{ActionContext, ExtensionRequest, ExtensionResponse},
+        indexing::App,
+        result::{HighlightRange, TextResult, TitleAndDescriptionResult, WLResult},
+    };
+
+    use super::*;
+
+    // Both formats have to give back the same value, compared through its JSON form since the
+    // protocol types don't implement PartialEq
+    fn assert_round_trips<T: Versioned>(value: &T) {
+        let expected = serde_json::to_value(value).unwrap();
+
+        for format in [WireFormat::Bincode, WireFormat::Json] {
+            let bytes = format.encode(value).unwrap();
+            let decoded: T = format.decode(&bytes).unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                expected,
+                "{} round trip",
+                format.name()
+            );
+        }
+    }
+
+    fn get_response() -> ExtensionResponse {
+        let dialog = DialogAction::new(
+            "notes",
+            "add",
+            "Add note",
+            "Add",
+            vec![Field::new_input(
+                "text",
+                InputField::new("", "Text", "The note").placeholder("Buy milk"),
+            )],
+        );
+
+        ExtensionResponse::new(vec![
+            WLResult::new_text(
+                TextResult::new("Copy", Action::new_copy(CopyAction::new("ünïcödé 🐈")))
+                    .highlights(vec![HighlightRange::new(0, 2)]),
+            ),
+            WLResult::new_divider(),
+            WLResult::new_title_and_description(
+                TitleAndDescriptionResult::new(
+                    "Open",
+                    "https://example.com",
+                    Action::new_open_url(OpenURLAction::new("https://example.com"))
+                        .ask_confirmation(true),
+                )
+                .icon("/tmp/icon.png")
+                .tint("#FF0000"),
+            ),
+            WLResult::new_text(TextResult::new(
+                "Run",
+                Action::new_extension(
+                    ExtensionAction::new("notes", "run").args(vec![String::from("1")]),
+                ),
+            )),
+            WLResult::new_text(TextResult::new("Add", Action::new_dialog(dialog))),
+        ])
+        .args(vec![String::from("page"), String::new()])
+    }
+
+    #[test]
+    fn extension_request_round_trips_in_both_formats() {
+        assert_round_trips(&ExtensionRequest::new(
+            "notes",
+            ActionContext::ResultsRequest,
+        ));
+
+        assert_round_trips(
+            &ExtensionRequest::new("notes", ActionContext::RunAction)
+                .search_text("  hello wörld ")
+                .extension_action("open")
+                .args(vec![String::from("a"), String::from("")])
+                .session_id("abc-123"),
+        );
+    }
+
+    #[test]
+    fn extension_response_round_trips_in_both_formats() {
+        assert_round_trips(&ExtensionResponse::new(Vec::new()));
+        assert_round_trips(&get_response());
+    }
+
+    #[test]
+    fn formats_decode_to_the_same_response() {
+        let response = get_response();
+
+        let from_bincode: ExtensionResponse = WireFormat::Bincode
+            .decode(&WireFormat::Bincode.encode(&response).unwrap())
+            .unwrap();
+        let from_json: ExtensionResponse = WireFormat::Json
+            .decode(&WireFormat::Json.encode(&response).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(from_bincode).unwrap(),
+            serde_json::to_value(from_json).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_bincode_has_the_header() {
+        let request = ExtensionRequest::new("notes", ActionContext::DialogResponse);
+
+        let bincode = WireFormat::Bincode.encode(&request).unwrap();
+        let json = WireFormat::Json.encode(&request).unwrap();
+
+        assert_eq!(bincode[..PROTOCOL_MAGIC.len()], PROTOCOL_MAGIC);
+        assert_eq!(get_version(&bincode), PROTOCOL_VERSION);
+        assert!(serde_json::from_slice::<Value>(&json).is_ok());
+        assert_eq!(WireFormat::Json.get_version(&json), PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn apps_index_round_trips_and_reads_the_previous_shape() {
+        let apps = vec![
+            App::new(
+                "firefox.desktop",
+                "Firefox",
+                "/usr/share/applications/firefox.desktop",
+            )
+            .command("firefox"),
+            App::new(
+                "files.desktop",
+                "Files",
+                "/usr/share/applications/files.desktop",
+            ),
+        ];
+
+        assert_round_trips(&apps);
+
+        let previous: Vec<App> = decode(&encode_version(&apps, 5).unwrap()).unwrap();
+        assert_eq!(previous[0].path, apps[0].path);
+        assert_eq!(previous[0].command, None);
+
+        // JSON written before the command existed still reads
+        let json = r#"[{"id": "a", "title": "A", "icon": null, "path": "/a.desktop"}]"#;
+        let from_json: Vec<App> = WireFormat::Json.decode(json.as_bytes()).unwrap();
+        assert_eq!(from_json[0].command, None);
+    }
+}
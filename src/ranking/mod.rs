@@ -0,0 +1,460 @@
+use std::{cmp::Ordering, fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{apps::try_get_apps, extensions::try_get_extensions},
+    error::WhiskersError,
+    extension::Extension,
+    indexing::App,
+    paths::get_frecency_path,
+    protocol::{decode, encode, Versioned},
+    storage::{lock_exclusive, write_atomic},
+    utils::{fuzzy_score, get_timestamp},
+};
+
+// Only the latest launches are kept to weigh how recent the usage is, the count keeps growing
+const MAX_LAUNCH_SAMPLES: usize = 10;
+const MAX_FRECENCY_ENTRIES: usize = 1000;
+
+const DAY: u64 = 24 * 60 * 60;
+
+// Launches from a query the current one is a prefix of weigh more than launches from any query
+const PREFIX_MATCH_MULTIPLIER: i64 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RankedKind {
+    App,
+    Extension,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FrecencyStore {
+    pub entries: Vec<FrecencyEntry>,
+}
+
+impl Versioned for FrecencyStore {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FrecencyEntry {
+    pub kind: RankedKind,
+    pub item_id: String,
+    pub query: String,
+    pub count: u64,
+    pub launches: Vec<u64>,
+}
+
+impl FrecencyEntry {
+    // The launch count weighted by the average age bucket of the latest launches
+    pub fn get_score(&self, now: u64) -> i64 {
+        if self.launches.is_empty() {
+            return 0;
+        }
+
+        let total_weight: i64 = self
+            .launches
+            .iter()
+            .map(|launched_at| get_recency_weight(now.saturating_sub(*launched_at)))
+            .sum();
+
+        self.count as i64 * total_weight / self.launches.len() as i64
+    }
+}
+
+impl FrecencyStore {
+    pub fn record(
+        &mut self,
+        kind: RankedKind,
+        item_id: impl Into<String>,
+        query: impl Into<String>,
+        now: u64,
+    ) {
+        let item_id = item_id.into();
+        let query = normalize_query(query);
+
+        let position = self.entries.iter().position(|entry| {
+            entry.kind == kind && entry.item_id == item_id && entry.query == query
+        });
+
+        let mut entry = match position {
+            Some(position) => self.entries.remove(position),
+            None => FrecencyEntry {
+                kind,
+                item_id,
+                query,
+                count: 0,
+                launches: Vec::new(),
+            },
+        };
+
+        entry.count += 1;
+        entry.launches.insert(0, now);
+        entry.launches.truncate(MAX_LAUNCH_SAMPLES);
+
+        // Most recently used entries first so the least used ones are dropped when it's full
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_FRECENCY_ENTRIES);
+    }
+
+    pub fn get_frecency(&self, kind: RankedKind, item_id: &str, query: &str, now: u64) -> i64 {
+        let query = normalize_query(query);
+
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == kind && entry.item_id == item_id)
+            .map(|entry| {
+                let score = entry.get_score(now);
+
+                if !query.is_empty() && entry.query.starts_with(&query) {
+                    score * PREFIX_MATCH_MULTIPLIER
+                } else {
+                    score
+                }
+            })
+            .sum()
+    }
+
+    pub fn remove(&mut self, kind: RankedKind, item_id: &str) {
+        self.entries
+            .retain(|entry| !(entry.kind == kind && entry.item_id == item_id));
+    }
+}
+
+// Firefox style buckets, launches from the last days count the most
+pub fn get_recency_weight(age: u64) -> i64 {
+    match age {
+        age if age <= 4 * DAY => 100,
+        age if age <= 14 * DAY => 70,
+        age if age <= 31 * DAY => 50,
+        age if age <= 90 * DAY => 30,
+        _ => 10,
+    }
+}
+
+fn normalize_query(query: impl Into<String>) -> String {
+    query.into().trim().to_lowercase()
+}
+
+pub fn try_get_frecency_store() -> Result<FrecencyStore, WhiskersError> {
+    let _lock = lock_exclusive(get_frecency_path())?;
+    read_frecency_store()
+}
+
+pub fn get_frecency_store() -> FrecencyStore {
+    try_get_frecency_store().expect("Error getting frecency store")
+}
+
+pub fn try_record_launch(
+    kind: RankedKind,
+    item_id: impl Into<String>,
+    query: impl Into<String>,
+) -> Result<(), WhiskersError> {
+    let path = get_frecency_path();
+    let _lock = lock_exclusive(&path)?;
+
+    let mut store = read_frecency_store()?;
+    store.record(kind, item_id, query, get_timestamp());
+
+    write_atomic(&path, encode(&store)?)
+}
+
+pub fn record_launch(kind: RankedKind, item_id: impl Into<String>, query: impl Into<String>) {
+    try_record_launch(kind, item_id, query).expect("Error recording launch");
+}
+
+pub fn try_clear_frecency() -> Result<(), WhiskersError> {
+    let path = get_frecency_path();
+    let _lock = lock_exclusive(&path)?;
+    write_atomic(&path, encode(&FrecencyStore::default())?)
+}
+
+pub fn clear_frecency() {
+    try_clear_frecency().expect("Error clearing frecency");
+}
+
+// Sorts by score, then by title and id so that equal scores always come out in the same order
+pub fn rank<T>(
+    items: Vec<T>,
+    query: &str,
+    kind: RankedKind,
+    store: &FrecencyStore,
+    now: u64,
+    get_id: impl Fn(&T) -> &str,
+    get_texts: impl Fn(&T) -> Vec<&str>,
+) -> Vec<(T, i64)> {
+    let query = query.trim();
+
+    let mut ranked: Vec<(T, i64)> = items
+        .into_iter()
+        .filter_map(|item| {
+            let match_score = if query.is_empty() {
+                0
+            } else {
+                get_texts(&item)
+                    .into_iter()
+                    .filter_map(|text| fuzzy_score(text, query))
+                    .max()?
+            };
+
+            let score = match_score + store.get_frecency(kind, get_id(&item), query, now);
+            Some((item, score))
+        })
+        .collect();
+
+    ranked.sort_by(|(a, a_score), (b, b_score)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| compare_texts(&get_texts(a), &get_texts(b)))
+            .then_with(|| get_id(a).cmp(get_id(b)))
+    });
+
+    ranked
+}
+
+pub fn rank_apps_with(
+    apps: Vec<App>,
+    query: &str,
+    store: &FrecencyStore,
+    now: u64,
+) -> Vec<(App, i64)> {
+    rank(
+        apps,
+        query,
+        RankedKind::App,
+        store,
+        now,
+        |app| &app.id,
+        |app| vec![&app.title],
+    )
+}
+
+pub fn rank_extensions_with(
+    extensions: Vec<Extension>,
+    query: &str,
+    store: &FrecencyStore,
+    now: u64,
+) -> Vec<(Extension, i64)> {
+    rank(
+        extensions,
+        query,
+        RankedKind::Extension,
+        store,
+        now,
+        |extension| &extension.id,
+        |extension| vec![&extension.name, &extension.keyword],
+    )
+}
+
+pub fn try_rank_apps(query: impl Into<String>) -> Result<Vec<(App, i64)>, WhiskersError> {
+    let store = try_get_frecency_store()?;
+    let apps = try_get_apps()?;
+
+    Ok(rank_apps_with(apps, &query.into(), &store, get_timestamp()))
+}
+
+pub fn rank_apps(query: impl Into<String>) -> Vec<(App, i64)> {
+    try_rank_apps(query).expect("Error ranking apps")
+}
+
+pub fn try_rank_extensions(
+    query: impl Into<String>,
+) -> Result<Vec<(Extension, i64)>, WhiskersError> {
+    let store = try_get_frecency_store()?;
+    let extensions = try_get_extensions()?;
+
+    Ok(rank_extensions_with(
+        extensions,
+        &query.into(),
+        &store,
+        get_timestamp(),
+    ))
+}
+
+pub fn rank_extensions(query: impl Into<String>) -> Vec<(Extension, i64)> {
+    try_rank_extensions(query).expect("Error ranking extensions")
+}
+
+fn compare_texts(a: &[&str], b: &[&str]) -> Ordering {
+    let a = a
+        .first()
+        .map(|text| text.to_lowercase())
+        .unwrap_or_default();
+    let b = b
+        .first()
+        .map(|text| text.to_lowercase())
+        .unwrap_or_default();
+    a.cmp(&b)
+}
+
+// A missing or unreadable store starts over empty
+fn read_frecency_store() -> Result<FrecencyStore, WhiskersError> {
+    match fs::read(get_frecency_path()) {
+        Ok(bytes) => match decode(&bytes) {
+            Ok(store) => Ok(store),
+            Err(WhiskersError::Decode(_)) => Ok(FrecencyStore::default()),
+            Err(error) => Err(error),
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(FrecencyStore::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn get_apps() -> Vec<App> {
+        vec![
+            App::new("firefox", "Firefox", "/usr/bin/firefox"),
+            App::new("files", "Files", "/usr/bin/nautilus"),
+            App::new("terminal", "Terminal", "/usr/bin/kgx"),
+        ]
+    }
+
+    fn get_ids(ranked: &[(App, i64)]) -> Vec<&str> {
+        ranked.iter().map(|(app, _)| app.id.as_str()).collect()
+    }
+
+    fn get_app_score(ranked: &[(App, i64)], id: &str) -> i64 {
+        ranked
+            .iter()
+            .find(|(app, _)| app.id == id)
+            .map(|(_, score)| *score)
+            .unwrap()
+    }
+
+    #[test]
+    fn equal_scores_are_sorted_by_title_then_id() {
+        let mut apps = get_apps();
+        apps.push(App::new(
+            "firefox-nightly",
+            "firefox",
+            "/usr/bin/firefox-nightly",
+        ));
+
+        let ranked = rank_apps_with(apps.to_owned(), "", &FrecencyStore::default(), NOW);
+
+        assert!(ranked.iter().all(|(_, score)| *score == 0));
+        assert_eq!(
+            get_ids(&ranked),
+            vec!["files", "firefox", "firefox-nightly", "terminal"]
+        );
+
+        // The input order doesn't matter
+        apps.reverse();
+        let reversed = rank_apps_with(apps, "", &FrecencyStore::default(), NOW);
+        assert_eq!(get_ids(&reversed), get_ids(&ranked));
+    }
+
+    #[test]
+    fn query_filters_apps_that_do_not_match() {
+        let ranked = rank_apps_with(get_apps(), "term", &FrecencyStore::default(), NOW);
+
+        assert_eq!(get_ids(&ranked), vec!["terminal"]);
+        assert_eq!(
+            ranked[0].1,
+            fuzzy_score("Terminal", "term").unwrap(),
+            "no launches means only the match score counts"
+        );
+    }
+
+    #[test]
+    fn launches_from_a_query_starting_with_the_current_one_count_double() {
+        let mut store = FrecencyStore::default();
+        store.record(RankedKind::App, "firefox", "firefox", NOW);
+        store.record(RankedKind::App, "files", "nautilus", NOW);
+
+        let ranked = rank_apps_with(get_apps(), "fi", &store, NOW);
+
+        assert_eq!(
+            get_app_score(&ranked, "firefox"),
+            fuzzy_score("Firefox", "fi").unwrap() + 100 * PREFIX_MATCH_MULTIPLIER
+        );
+        assert_eq!(
+            get_app_score(&ranked, "files"),
+            fuzzy_score("Files", "fi").unwrap() + 100
+        );
+    }
+
+    #[test]
+    fn recorded_queries_are_trimmed_and_lowercased() {
+        let mut store = FrecencyStore::default();
+        store.record(RankedKind::App, "firefox", "  FireFox ", NOW);
+
+        assert_eq!(store.entries[0].query, "firefox");
+        assert_eq!(
+            store.get_frecency(RankedKind::App, "firefox", " FI", NOW),
+            100 * PREFIX_MATCH_MULTIPLIER
+        );
+        assert_eq!(
+            store.get_frecency(RankedKind::Extension, "firefox", "fi", NOW),
+            0
+        );
+    }
+
+    #[test]
+    fn older_launches_fall_into_lower_buckets() {
+        let buckets = [
+            (0, 100),
+            (4 * DAY, 100),
+            (4 * DAY + 1, 70),
+            (14 * DAY, 70),
+            (14 * DAY + 1, 50),
+            (31 * DAY, 50),
+            (31 * DAY + 1, 30),
+            (90 * DAY, 30),
+            (90 * DAY + 1, 10),
+            (1000 * DAY, 10),
+        ];
+
+        for (age, weight) in buckets {
+            assert_eq!(get_recency_weight(age), weight, "age of {} seconds", age);
+        }
+
+        // A launch in the future, from a clock that went back, counts as a recent one
+        let mut store = FrecencyStore::default();
+        store.record(RankedKind::App, "files", "", NOW + DAY);
+        assert_eq!(store.get_frecency(RankedKind::App, "files", "", NOW), 100);
+    }
+
+    #[test]
+    fn recency_orders_apps_launched_the_same_number_of_times() {
+        let mut store = FrecencyStore::default();
+        store.record(RankedKind::App, "terminal", "", NOW - DAY);
+        store.record(RankedKind::App, "files", "", NOW - 20 * DAY);
+        store.record(RankedKind::App, "firefox", "", NOW - 200 * DAY);
+
+        let ranked = rank_apps_with(get_apps(), "", &store, NOW);
+
+        assert_eq!(
+            ranked
+                .iter()
+                .map(|(app, score)| (app.id.as_str(), *score))
+                .collect::<Vec<_>>(),
+            vec![("terminal", 100), ("files", 50), ("firefox", 10)]
+        );
+    }
+
+    #[test]
+    fn score_is_the_count_times_the_average_weight() {
+        let mut store = FrecencyStore::default();
+        store.record(RankedKind::App, "files", "", NOW - 20 * DAY);
+        store.record(RankedKind::App, "files", "", NOW);
+
+        assert_eq!(store.entries[0].count, 2);
+        assert_eq!(store.entries[0].get_score(NOW), 2 * (100 + 50) / 2);
+
+        // Only the latest launches are sampled while the count keeps growing
+        for _ in 0..MAX_LAUNCH_SAMPLES {
+            store.record(RankedKind::App, "files", "", NOW);
+        }
+
+        assert_eq!(store.entries[0].launches.len(), MAX_LAUNCH_SAMPLES);
+        assert_eq!(
+            store.entries[0].get_score(NOW),
+            (MAX_LAUNCH_SAMPLES as i64 + 2) * 100
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::action::Action;
+use crate::{action::Action, utils::fuzzy_indices};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WLResult {
@@ -46,12 +46,48 @@ pub enum ResultType {
     Divider,
 }
 
+// Char range of the text that matched the search, from `start` up to but not including `end`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl HighlightRange {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+// Merges the matched char indices into ranges of consecutive chars
+pub fn get_highlight_ranges(indices: &[usize]) -> Vec<HighlightRange> {
+    let mut indices = indices.to_vec();
+    indices.sort_unstable();
+    indices.dedup();
+
+    let mut ranges = Vec::<HighlightRange>::new();
+
+    for index in indices {
+        match ranges.last_mut() {
+            Some(range) if range.end == index => range.end = index + 1,
+            _ => ranges.push(HighlightRange::new(index, index + 1)),
+        }
+    }
+
+    ranges
+}
+
+fn get_match_highlights(text: &str, search_text: &str) -> Option<Vec<HighlightRange>> {
+    fuzzy_indices(text, search_text).map(|(_, indices)| get_highlight_ranges(&indices))
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextResult {
     pub icon: Option<String>,
     pub tint: Option<String>,
     pub text: String,
     pub action: Action,
+    pub highlights: Option<Vec<HighlightRange>>,
 }
 
 impl TextResult {
@@ -61,6 +97,7 @@ impl TextResult {
             tint: None,
             text: text.into(),
             action,
+            highlights: None,
         }
     }
 
@@ -73,6 +110,17 @@ impl TextResult {
         self.tint = Some(tint.into());
         self.to_owned()
     }
+
+    pub fn highlights(&mut self, highlights: Vec<HighlightRange>) -> Self {
+        self.highlights = Some(highlights);
+        self.to_owned()
+    }
+
+    // Highlights the chars of the text that fuzzy match the search text
+    pub fn highlight_matches(&mut self, search_text: impl Into<String>) -> Self {
+        self.highlights = get_match_highlights(&self.text, &search_text.into());
+        self.to_owned()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -82,6 +130,7 @@ pub struct TitleAndDescriptionResult {
     pub title: String,
     pub description: String,
     pub action: Action,
+    pub highlights: Option<Vec<HighlightRange>>,
 }
 
 impl TitleAndDescriptionResult {
@@ -92,6 +141,7 @@ impl TitleAndDescriptionResult {
             title: title.into(),
             description: description.into(),
             action,
+            highlights: None,
         }
     }
 
@@ -104,4 +154,15 @@ impl TitleAndDescriptionResult {
         self.tint = Some(tint.into());
         self.to_owned()
     }
+
+    pub fn highlights(&mut self, highlights: Vec<HighlightRange>) -> Self {
+        self.highlights = Some(highlights);
+        self.to_owned()
+    }
+
+    // Highlights the chars of the title that fuzzy match the search text
+    pub fn highlight_matches(&mut self, search_text: impl Into<String>) -> Self {
+        self.highlights = get_match_highlights(&self.title, &search_text.into());
+        self.to_owned()
+    }
 }
@@ -0,0 +1,275 @@
+use crate::{
+    api::manifest::{find_keyword_owner, get_extension_keyword, KeywordOwner},
+    extension::Extension,
+    settings::{SearchEngine, Settings},
+    shortcut::Shortcut,
+    utils::{get_search, get_search_without_keyword, Search},
+};
+
+#[derive(Debug, Clone)]
+pub enum QueryTarget {
+    Apps {
+        search: Search,
+    },
+    DefaultSearchEngine {
+        engine: Option<SearchEngine>,
+        search: Search,
+    },
+    SearchEngine {
+        engine: SearchEngine,
+        search: Search,
+    },
+    Extension {
+        extension: Extension,
+        search: Search,
+    },
+    Shortcut {
+        shortcut: Shortcut,
+        search: Search,
+    },
+}
+
+// A keyword claimed by more than one owner. Owners are in precedence order so the first one is
+// the one queries get routed to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeywordCollision {
+    pub keyword: String,
+    pub owners: Vec<KeywordOwner>,
+}
+
+impl QueryTarget {
+    pub fn get_search(&self) -> &Search {
+        match self {
+            QueryTarget::Apps { search }
+            | QueryTarget::DefaultSearchEngine { search, .. }
+            | QueryTarget::SearchEngine { search, .. }
+            | QueryTarget::Extension { search, .. }
+            | QueryTarget::Shortcut { search, .. } => search,
+        }
+    }
+}
+
+// The keyword goes to the search keyword first, then to the search engines and then to the
+// extensions. Anything without a known keyword is an app search of the whole text.
+pub fn resolve_query(
+    text: impl Into<String>,
+    settings: &Settings,
+    extensions: &[Extension],
+) -> QueryTarget {
+    resolve_query_with_shortcuts(text, settings, extensions, &[])
+}
+
+// Same as resolve_query with the shortcuts coming after the extensions
+pub fn resolve_query_with_shortcuts(
+    text: impl Into<String>,
+    settings: &Settings,
+    extensions: &[Extension],
+    shortcuts: &[Shortcut],
+) -> QueryTarget {
+    let search = get_search(text);
+
+    let keyword = match &search.keyword {
+        Some(keyword) if !keyword.is_empty() => keyword.to_owned(),
+        _ => return QueryTarget::Apps { search },
+    };
+
+    match find_keyword_owner(&keyword, settings, extensions) {
+        Some(KeywordOwner::SearchKeyword) => QueryTarget::DefaultSearchEngine {
+            engine: settings
+                .search_engines
+                .iter()
+                .find(|engine| engine.id == settings.default_search_engine)
+                .cloned(),
+            search,
+        },
+        Some(KeywordOwner::SearchEngine(id)) => match settings
+            .search_engines
+            .iter()
+            .find(|engine| engine.id == id)
+        {
+            Some(engine) => QueryTarget::SearchEngine {
+                engine: engine.to_owned(),
+                search,
+            },
+            None => QueryTarget::Apps { search },
+        },
+        Some(KeywordOwner::Extension(id)) => {
+            match extensions.iter().find(|extension| extension.id == id) {
+                Some(extension) => QueryTarget::Extension {
+                    extension: extension.to_owned(),
+                    search,
+                },
+                None => QueryTarget::Apps { search },
+            }
+        }
+        // Shortcuts aren't known to find_keyword_owner so they're only looked up without an owner
+        _ => match shortcuts
+            .iter()
+            .find(|shortcut| shortcut.keyword == keyword)
+        {
+            Some(shortcut) => QueryTarget::Shortcut {
+                shortcut: shortcut.to_owned(),
+                search,
+            },
+            // Text starting with an unknown keyword is searched as a whole
+            None => QueryTarget::Apps {
+                search: get_search_without_keyword(search.raw),
+            },
+        },
+    }
+}
+
+// Lists every keyword that more than one owner claims
+pub fn get_keyword_collisions(
+    settings: &Settings,
+    extensions: &[Extension],
+) -> Vec<KeywordCollision> {
+    get_keyword_collisions_with_shortcuts(settings, extensions, &[])
+}
+
+pub fn get_keyword_collisions_with_shortcuts(
+    settings: &Settings,
+    extensions: &[Extension],
+    shortcuts: &[Shortcut],
+) -> Vec<KeywordCollision> {
+    let mut claims = vec![(
+        settings.search_keyword.to_owned(),
+        KeywordOwner::SearchKeyword,
+    )];
+
+    for engine in &settings.search_engines {
+        claims.push((
+            engine.keyword.to_owned(),
+            KeywordOwner::SearchEngine(engine.id),
+        ));
+    }
+
+    for extension in extensions {
+        claims.push((
+            get_extension_keyword(extension, settings),
+            KeywordOwner::Extension(extension.id.to_owned()),
+        ));
+    }
+
+    for shortcut in shortcuts {
+        claims.push((
+            shortcut.keyword.to_owned(),
+            KeywordOwner::Shortcut(shortcut.id),
+        ));
+    }
+
+    let mut collisions = Vec::<KeywordCollision>::new();
+
+    for (keyword, owner) in claims {
+        match collisions
+            .iter_mut()
+            .find(|collision| collision.keyword == keyword)
+        {
+            Some(collision) => collision.owners.push(owner),
+            None => collisions.push(KeywordCollision {
+                keyword,
+                owners: vec![owner],
+            }),
+        }
+    }
+
+    collisions.retain(|collision| collision.owners.len() > 1);
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::manifest::parse_manifest, settings::get_default_settings};
+
+    use super::*;
+
+    fn get_extension() -> Extension {
+        parse_manifest(
+            r#"{"id": "notes", "name": "Notes", "description": "Notes", "keyword": "n"}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn search_keyword_goes_to_the_default_engine() {
+        let settings = get_default_settings();
+
+        match resolve_query("s foo", &settings, &[]) {
+            QueryTarget::DefaultSearchEngine { engine, search } => {
+                assert_eq!(engine.unwrap().id, settings.default_search_engine);
+                assert_eq!(search.search_text, "foo");
+            }
+            target => panic!("Unexpected target {:?}", target),
+        }
+    }
+
+    #[test]
+    fn engine_keyword_goes_to_the_engine() {
+        let settings = get_default_settings();
+
+        match resolve_query("gs foo", &settings, &[]) {
+            QueryTarget::SearchEngine { engine, search } => {
+                assert_eq!(engine.keyword, "gs");
+                assert_eq!(search.keyword.as_deref(), Some("gs"));
+                assert_eq!(search.search_text, "foo");
+            }
+            target => panic!("Unexpected target {:?}", target),
+        }
+    }
+
+    #[test]
+    fn extension_keyword_goes_to_the_extension() {
+        let settings = get_default_settings();
+
+        match resolve_query("n buy milk", &settings, &[get_extension()]) {
+            QueryTarget::Extension { extension, search } => {
+                assert_eq!(extension.id, "notes");
+                assert_eq!(search.terms, vec!["buy", "milk"]);
+            }
+            target => panic!("Unexpected target {:?}", target),
+        }
+    }
+
+    #[test]
+    fn unknown_keyword_searches_the_whole_text() {
+        let settings = get_default_settings();
+
+        match resolve_query("firefox private", &settings, &[get_extension()]) {
+            QueryTarget::Apps { search } => {
+                assert_eq!(search.keyword, None);
+                assert_eq!(search.search_text, "firefox private");
+            }
+            target => panic!("Unexpected target {:?}", target),
+        }
+    }
+
+    #[test]
+    fn leading_space_is_an_app_search() {
+        let settings = get_default_settings();
+
+        match resolve_query(" s foo", &settings, &[]) {
+            QueryTarget::Apps { search } => assert_eq!(search.search_text, "s foo"),
+            target => panic!("Unexpected target {:?}", target),
+        }
+    }
+
+    #[test]
+    fn shortcuts_come_after_the_other_owners() {
+        let settings = get_default_settings();
+        let shortcuts = [
+            Shortcut::new_url("gs", "Shadowed", "https://example.com/%s"),
+            Shortcut::new_url("docs", "Docs", "https://docs.rs/%s"),
+        ];
+
+        let target = resolve_query_with_shortcuts("gs foo", &settings, &[], &shortcuts);
+        assert!(matches!(target, QueryTarget::SearchEngine { .. }));
+
+        match resolve_query_with_shortcuts("docs serde", &settings, &[], &shortcuts) {
+            QueryTarget::Shortcut { shortcut, search } => {
+                assert_eq!(shortcut.name, "Docs");
+                assert_eq!(search.search_text, "serde");
+            }
+            target => panic!("Unexpected target {:?}", target),
+        }
+    }
+}
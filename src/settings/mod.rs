@@ -1,6 +1,16 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{fmt, path::PathBuf};
 
-use crate::paths::get_app_resources_icons_dir;
+use crate::{
+    action::{Action, OpenURLAction},
+    extension::SettingType,
+    indexing::desktop::get_current_locale,
+    paths::get_app_resources_icons_dir,
+    result::{TextResult, WLResult},
+    utils::expand_query_template,
+};
+
+pub const DEFAULT_SEARCH_LANGUAGE: &str = "en";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Settings {
@@ -60,6 +70,56 @@ pub struct SearchEngine {
     pub keyword: String,
     pub name: String,
     pub search_query: String,
+    // Suggestions send the query to the engine as it's typed so none of the defaults have one
+    #[serde(default)]
+    pub suggestions_query: Option<String>,
+}
+
+impl SearchEngine {
+    pub fn build_url(&self, query: impl Into<String>) -> String {
+        self.build_url_with_lang(query, get_search_language())
+    }
+
+    pub fn build_url_with_lang(&self, query: impl Into<String>, lang: impl Into<String>) -> String {
+        expand_query_template(&self.search_query, &query.into(), &lang.into())
+    }
+
+    pub fn build_suggestions_url(&self, query: impl Into<String>) -> Option<String> {
+        self.suggestions_query.as_ref().map(|suggestions_query| {
+            expand_query_template(suggestions_query, &query.into(), &get_search_language())
+        })
+    }
+
+    pub fn get_action(&self, query: impl Into<String>) -> Action {
+        Action::new_open_url(OpenURLAction::new(self.build_url(query)))
+    }
+
+    pub fn get_result(&self, query: impl Into<String>) -> WLResult {
+        let query = query.into();
+
+        let mut result = TextResult::new(
+            format!("Search {} for \"{}\"", self.name, query),
+            self.get_action(&query),
+        );
+
+        if let Some(icon_path) = &self.icon_path {
+            result.icon(icon_path);
+        }
+
+        if self.tint_icon {
+            result.tint("accent");
+        }
+
+        WLResult::new_text(result)
+    }
+}
+
+// The language part of the messages locale, e.g. "pt" for "pt_BR.UTF-8"
+pub fn get_search_language() -> String {
+    get_current_locale()
+        .and_then(|locale| locale.split(['_', '@']).next().map(|lang| lang.to_owned()))
+        .filter(|lang| !lang.is_empty())
+        .unwrap_or(String::from(DEFAULT_SEARCH_LANGUAGE))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -80,7 +140,210 @@ pub struct Theme {
 pub struct ExtensionSetting {
     pub extension_id: String,
     pub setting_id: String,
-    pub setting_value: String,
+    pub setting_value: SettingValue,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum SettingValue {
+    Text(String),
+    Bool(bool),
+    Number(f64),
+    Select(String),
+    Path(String),
+    List(Vec<String>),
+}
+
+// Mirrors SettingValue so that binary formats keep using the tagged representation
+#[derive(Deserialize)]
+enum TaggedSettingValue {
+    Text(String),
+    Bool(bool),
+    Number(f64),
+    Select(String),
+    Path(String),
+    List(Vec<String>),
+}
+
+// Manifests are written by hand so plain JSON values are accepted as well as the tagged form
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlainSettingValue {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    List(Vec<String>),
+    Tagged(TaggedSettingValue),
+}
+
+impl From<TaggedSettingValue> for SettingValue {
+    fn from(value: TaggedSettingValue) -> Self {
+        match value {
+            TaggedSettingValue::Text(text) => SettingValue::Text(text),
+            TaggedSettingValue::Bool(bool) => SettingValue::Bool(bool),
+            TaggedSettingValue::Number(number) => SettingValue::Number(number),
+            TaggedSettingValue::Select(id) => SettingValue::Select(id),
+            TaggedSettingValue::Path(path) => SettingValue::Path(path),
+            TaggedSettingValue::List(list) => SettingValue::List(list),
+        }
+    }
+}
+
+impl From<PlainSettingValue> for SettingValue {
+    fn from(value: PlainSettingValue) -> Self {
+        match value {
+            PlainSettingValue::Bool(bool) => SettingValue::Bool(bool),
+            PlainSettingValue::Number(number) => SettingValue::Number(number),
+            PlainSettingValue::Text(text) => SettingValue::Text(text),
+            PlainSettingValue::List(list) => SettingValue::List(list),
+            PlainSettingValue::Tagged(tagged) => tagged.into(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SettingValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            PlainSettingValue::deserialize(deserializer).map(SettingValue::from)
+        } else {
+            TaggedSettingValue::deserialize(deserializer).map(SettingValue::from)
+        }
+    }
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingValue::Text(text) => write!(f, "{}", text),
+            SettingValue::Bool(bool) => write!(f, "{}", bool),
+            SettingValue::Number(number) => write!(f, "{}", number),
+            SettingValue::Select(id) => write!(f, "{}", id),
+            SettingValue::Path(path) => write!(f, "{}", path),
+            SettingValue::List(list) => write!(f, "{}", list.join("\n")),
+        }
+    }
+}
+
+impl SettingValue {
+    // Converts the value to the variant the setting type expects, which is how string values from
+    // older settings files and manifests get their type
+    pub fn coerce(self, setting_type: &SettingType) -> Self {
+        match (setting_type, self) {
+            (SettingType::Input | SettingType::TextArea, SettingValue::Text(text)) => {
+                SettingValue::Text(text)
+            }
+            (SettingType::Input | SettingType::TextArea, value) => {
+                SettingValue::Text(value.to_string())
+            }
+            (SettingType::Toggle, SettingValue::Text(text)) => match text.trim().parse::<bool>() {
+                Ok(bool) => SettingValue::Bool(bool),
+                Err(_) => SettingValue::Text(text),
+            },
+            (SettingType::Number, SettingValue::Text(text)) => match text.trim().parse::<f64>() {
+                Ok(number) => SettingValue::Number(number),
+                Err(_) => SettingValue::Text(text),
+            },
+            (SettingType::Select, SettingValue::Text(text)) => SettingValue::Select(text),
+            (SettingType::Path, SettingValue::Text(text)) => SettingValue::Path(text),
+            (SettingType::List, SettingValue::Text(text)) => SettingValue::List(
+                text.lines()
+                    .map(|line| line.trim().to_owned())
+                    .filter(|line| !line.is_empty())
+                    .collect(),
+            ),
+            (_, value) => value,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            SettingValue::Text(text) => Some(text),
+            SettingValue::Select(id) => Some(id),
+            SettingValue::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            SettingValue::Bool(bool) => Some(*bool),
+            SettingValue::Text(text) => text.trim().parse::<bool>().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            SettingValue::Number(number) => Some(*number),
+            SettingValue::Text(text) => text.trim().parse::<f64>().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<Vec<String>> {
+        match self {
+            SettingValue::List(list) => Some(list.to_owned()),
+            SettingValue::Text(text) => Some(text.lines().map(|line| line.to_owned()).collect()),
+            _ => None,
+        }
+    }
+}
+
+pub trait FromSettingValue: Sized {
+    fn from_setting_value(value: &SettingValue) -> Option<Self>;
+}
+
+impl FromSettingValue for SettingValue {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        Some(value.to_owned())
+    }
+}
+
+impl FromSettingValue for String {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        Some(value.to_string())
+    }
+}
+
+impl FromSettingValue for bool {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl FromSettingValue for f64 {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        value.as_number()
+    }
+}
+
+impl FromSettingValue for i64 {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        value
+            .as_number()
+            .filter(|number| number.fract() == 0.0)
+            .map(|number| number as i64)
+    }
+}
+
+impl FromSettingValue for usize {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        i64::from_setting_value(value).and_then(|number| usize::try_from(number).ok())
+    }
+}
+
+impl FromSettingValue for PathBuf {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::Path(path) | SettingValue::Text(path) => Some(PathBuf::from(path)),
+            _ => None,
+        }
+    }
+}
+
+impl FromSettingValue for Vec<String> {
+    fn from_setting_value(value: &SettingValue) -> Option<Self> {
+        value.as_list()
+    }
 }
 
 // =================================================================
@@ -201,6 +464,7 @@ fn default_search_engines() -> Vec<SearchEngine> {
             keyword: String::from("gs"),
             name: String::from("Google"),
             search_query: String::from("https://www.google.com/search?q=%s"),
+            suggestions_query: None,
         },
         SearchEngine {
             id: 1,
@@ -214,6 +478,7 @@ fn default_search_engines() -> Vec<SearchEngine> {
             keyword: String::from("ds"),
             name: String::from("DuckDuckGo"),
             search_query: String::from("https://duckduckgo.com/?q=%s"),
+            suggestions_query: None,
         },
         SearchEngine {
             id: 2,
@@ -227,6 +492,7 @@ fn default_search_engines() -> Vec<SearchEngine> {
             keyword: String::from("bs"),
             name: String::from("Brave"),
             search_query: String::from("https://search.brave.com/search?q=%s"),
+            suggestions_query: None,
         },
         SearchEngine {
             id: 3,
@@ -235,6 +501,7 @@ fn default_search_engines() -> Vec<SearchEngine> {
             keyword: String::from("ss"),
             name: String::from("Startpage"),
             search_query: String::from("https://www.startpage.com/do/dsearch?q=%s"),
+            suggestions_query: None,
         },
     ]
 }
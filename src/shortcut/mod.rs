@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{Action, OpenAppAction, OpenURLAction, RunCommandAction},
+    protocol::Versioned,
+    result::{TitleAndDescriptionResult, WLResult},
+    settings::get_search_language,
+    utils::expand_query_template,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Shortcut {
+    pub id: usize,
+    pub keyword: String,
+    pub name: String,
+    pub shortcut_type: ShortcutType,
+    pub target: String,
+    pub icon: Option<String>,
+}
+
+impl Versioned for Vec<Shortcut> {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ShortcutType {
+    App,
+    URL,
+    Command,
+    File,
+}
+
+impl Shortcut {
+    // The id is given when the shortcut is added
+    pub fn new(
+        keyword: impl Into<String>,
+        name: impl Into<String>,
+        shortcut_type: ShortcutType,
+        target: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: 0,
+            keyword: keyword.into(),
+            name: name.into(),
+            shortcut_type,
+            target: target.into(),
+            icon: None,
+        }
+    }
+
+    pub fn new_app(
+        keyword: impl Into<String>,
+        name: impl Into<String>,
+        app_id: impl Into<String>,
+    ) -> Self {
+        Self::new(keyword, name, ShortcutType::App, app_id)
+    }
+
+    pub fn new_url(
+        keyword: impl Into<String>,
+        name: impl Into<String>,
+        url: impl Into<String>,
+    ) -> Self {
+        Self::new(keyword, name, ShortcutType::URL, url)
+    }
+
+    pub fn new_command(
+        keyword: impl Into<String>,
+        name: impl Into<String>,
+        command: impl Into<String>,
+    ) -> Self {
+        Self::new(keyword, name, ShortcutType::Command, command)
+    }
+
+    pub fn new_file(
+        keyword: impl Into<String>,
+        name: impl Into<String>,
+        path: impl Into<String>,
+    ) -> Self {
+        Self::new(keyword, name, ShortcutType::File, path)
+    }
+
+    pub fn icon(&mut self, icon: impl Into<String>) -> Self {
+        self.icon = Some(icon.into());
+        self.to_owned()
+    }
+
+    // The args are the text typed after the keyword. URLs take them through the same placeholders
+    // as the search engines and commands replace %s with them quoted as a single shell argument.
+    pub fn get_action(&self, args: impl Into<String>) -> Action {
+        let args = args.into();
+
+        match self.shortcut_type {
+            ShortcutType::App => Action::new_open_app(OpenAppAction::new(&self.target)),
+            ShortcutType::URL => Action::new_open_url(OpenURLAction::new(expand_query_template(
+                &self.target,
+                &args,
+                &get_search_language(),
+            ))),
+            ShortcutType::Command => {
+                Action::new_run_command(RunCommandAction::new(self.get_command(&args)))
+            }
+            ShortcutType::File => Action::new_open_url(OpenURLAction::new(&self.target)),
+        }
+    }
+
+    pub fn get_result(&self, args: impl Into<String>) -> WLResult {
+        let args = args.into();
+
+        let description = match self.shortcut_type {
+            ShortcutType::URL => expand_query_template(&self.target, &args, &get_search_language()),
+            ShortcutType::Command => self.get_command(&args),
+            ShortcutType::App | ShortcutType::File => self.target.to_owned(),
+        };
+
+        let mut result =
+            TitleAndDescriptionResult::new(&self.name, description, self.get_action(&args));
+
+        if let Some(icon) = &self.icon {
+            result.icon(icon);
+        }
+
+        WLResult::new_title_and_description(result)
+    }
+
+    fn get_command(&self, args: &str) -> String {
+        self.target.replace("%s", &quote_shell_arg(args))
+    }
+}
+
+// Commands run through "sh -c" on Linux, where nothing is expanded inside single quotes
+#[cfg(not(target_os = "windows"))]
+fn quote_shell_arg(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+// Commands run through "cmd /C" on Windows, where a doubled quote is a literal quote
+#[cfg(target_os = "windows")]
+fn quote_shell_arg(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('"', "\"\""))
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_args_are_one_quoted_argument() {
+        let shortcut = Shortcut::new_command("f", "Find", "find ~ -name %s");
+        let action = shortcut.get_action("it's; rm -rf $HOME");
+        let command = action.run_command.unwrap().command;
+
+        assert_eq!(command, "find ~ -name 'it'\\''s; rm -rf $HOME'");
+
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(command.replace("find ~ -name", "printf %s"))
+            .output()
+            .unwrap();
+
+        assert_eq!(output.stdout, b"it's; rm -rf $HOME");
+    }
+
+    #[test]
+    fn command_description_is_the_command_that_runs() {
+        let shortcut = Shortcut::new_command("e", "Echo", "echo %s");
+        let result = shortcut.get_result("a b");
+        let result = result.title_and_description.unwrap();
+
+        assert_eq!(result.description, "echo 'a b'");
+        assert_eq!(
+            result.action.run_command.unwrap().command,
+            result.description
+        );
+    }
+}
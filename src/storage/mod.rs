@@ -0,0 +1,100 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use fs4::fs_std::FileExt;
+
+use crate::error::WhiskersError;
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Holds an advisory lock on `<path>.lock` until dropped
+pub struct FileLock {
+    file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+pub fn get_lock_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".lock");
+    path.with_file_name(file_name)
+}
+
+pub fn lock_exclusive(path: impl AsRef<Path>) -> Result<FileLock, WhiskersError> {
+    let file = open_lock_file(path.as_ref())?;
+    FileExt::lock_exclusive(&file)?;
+    Ok(FileLock { file })
+}
+
+pub fn lock_shared(path: impl AsRef<Path>) -> Result<FileLock, WhiskersError> {
+    let file = open_lock_file(path.as_ref())?;
+    FileExt::lock_shared(&file)?;
+    Ok(FileLock { file })
+}
+
+// A path next to `path` that no other thread or process uses at the same time
+pub fn get_temp_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+    temp_name.push(format!(
+        ".{}-{}.tmp",
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    path.with_file_name(temp_name)
+}
+
+// Writes to a temporary file next to `path` and renames it over the original so that readers
+// never observe a partially written file
+pub fn write_atomic(path: impl AsRef<Path>, bytes: impl AsRef<[u8]>) -> Result<(), WhiskersError> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = get_temp_path(path);
+    let result = write_and_rename(&temp_path, path, bytes.as_ref());
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+fn write_and_rename(temp_path: &Path, path: &Path, bytes: &[u8]) -> Result<(), WhiskersError> {
+    let mut file = File::create(temp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+fn open_lock_file(path: &Path) -> Result<File, WhiskersError> {
+    let lock_path = get_lock_path(path);
+
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)?;
+
+    Ok(file)
+}
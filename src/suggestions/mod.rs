@@ -0,0 +1,351 @@
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::{
+    error::{decode_error, http_error, WhiskersError},
+    result::{TextResult, WLResult},
+    settings::SearchEngine,
+};
+
+pub const DEFAULT_SUGGESTIONS_TIMEOUT: Duration = Duration::from_millis(1500);
+pub const DEFAULT_SUGGESTIONS_LIMIT: usize = 8;
+
+pub trait SuggestionProvider {
+    // Returns the body of the suggestions response
+    fn fetch(&self, url: &str) -> Result<String, WhiskersError>;
+
+    // The most suggestions get_suggestions returns
+    fn get_limit(&self) -> usize {
+        DEFAULT_SUGGESTIONS_LIMIT
+    }
+
+    fn get_suggestions(
+        &self,
+        engine: &SearchEngine,
+        query: &str,
+    ) -> Result<Vec<String>, WhiskersError> {
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut suggestions = match engine.build_suggestions_url(query) {
+            Some(url) => parse_suggestions(&self.fetch(&url)?)?,
+            None => Vec::new(),
+        };
+
+        suggestions.truncate(self.get_limit());
+        Ok(suggestions)
+    }
+
+    // Each suggestion searches the engine for the suggested text
+    fn get_suggestion_results(
+        &self,
+        engine: &SearchEngine,
+        query: &str,
+    ) -> Result<Vec<WLResult>, WhiskersError> {
+        let results = self
+            .get_suggestions(engine, query)?
+            .into_iter()
+            .map(|suggestion| {
+                let mut result = TextResult::new(&suggestion, engine.get_action(&suggestion))
+                    .highlight_matches(query);
+
+                if let Some(icon_path) = &engine.icon_path {
+                    result.icon(icon_path);
+                }
+
+                if engine.tint_icon {
+                    result.tint("accent");
+                }
+
+                WLResult::new_text(result)
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpSuggestionProvider {
+    pub timeout: Duration,
+    pub limit: usize,
+}
+
+impl HttpSuggestionProvider {
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_SUGGESTIONS_TIMEOUT,
+            limit: DEFAULT_SUGGESTIONS_LIMIT,
+        }
+    }
+
+    pub fn timeout(&mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.to_owned()
+    }
+
+    pub fn limit(&mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self.to_owned()
+    }
+}
+
+impl Default for HttpSuggestionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SuggestionProvider for HttpSuggestionProvider {
+    fn fetch(&self, url: &str) -> Result<String, WhiskersError> {
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+
+        agent
+            .get(url)
+            .call()
+            .map_err(http_error)?
+            .into_string()
+            .map_err(WhiskersError::from)
+    }
+
+    fn get_limit(&self) -> usize {
+        self.limit
+    }
+}
+
+// Reads the OpenSearch suggestions format, ["query", ["suggestion", ...], ...]. A plain list of
+// strings or of {"phrase": "..."} objects, which some engines return, is accepted as well.
+pub fn parse_suggestions(json: &str) -> Result<Vec<String>, WhiskersError> {
+    let value: Value = serde_json::from_str(json).map_err(decode_error)?;
+
+    let items = match &value {
+        Value::Array(items) => match items.as_slice() {
+            [Value::String(_), Value::Array(suggestions), ..] => suggestions,
+            _ => items,
+        },
+        _ => {
+            return Err(WhiskersError::Decode(String::from(
+                "Suggestions response is not a list",
+            )))
+        }
+    };
+
+    let mut suggestions = Vec::<String>::new();
+
+    for item in items {
+        let suggestion = match item {
+            Value::String(suggestion) => Some(suggestion.to_owned()),
+            Value::Object(object) => object
+                .get("phrase")
+                .and_then(|phrase| phrase.as_str())
+                .map(|phrase| phrase.to_owned()),
+            _ => None,
+        };
+
+        if let Some(suggestion) = suggestion.filter(|suggestion| !suggestion.is_empty()) {
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+    }
+
+    Ok(suggestions)
+}
+
+pub fn try_get_suggestion_results(
+    engine: &SearchEngine,
+    query: impl Into<String>,
+) -> Result<Vec<WLResult>, WhiskersError> {
+    HttpSuggestionProvider::new().get_suggestion_results(engine, &query.into())
+}
+
+// Suggestions are a nice to have so a failed request gives no results
+pub fn get_suggestion_results(engine: &SearchEngine, query: impl Into<String>) -> Vec<WLResult> {
+    try_get_suggestion_results(engine, query).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    // Answers every request with the same body and keeps the urls it was asked for
+    struct FakeProvider {
+        body: String,
+        limit: usize,
+        urls: RefCell<Vec<String>>,
+    }
+
+    impl FakeProvider {
+        fn new(body: &str) -> Self {
+            Self {
+                body: body.to_owned(),
+                limit: DEFAULT_SUGGESTIONS_LIMIT,
+                urls: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl SuggestionProvider for FakeProvider {
+        fn fetch(&self, url: &str) -> Result<String, WhiskersError> {
+            self.urls.borrow_mut().push(url.to_owned());
+            Ok(self.body.to_owned())
+        }
+
+        fn get_limit(&self) -> usize {
+            self.limit
+        }
+    }
+
+    fn get_engine(suggestions_query: Option<&str>) -> SearchEngine {
+        SearchEngine {
+            id: 0,
+            icon_path: Some(String::from("/tmp/engine.svg")),
+            tint_icon: true,
+            keyword: String::from("t"),
+            name: String::from("Test"),
+            search_query: String::from("https://example.com/search?q=%s"),
+            suggestions_query: suggestions_query.map(String::from),
+        }
+    }
+
+    #[test]
+    fn provider_fetches_the_engine_suggestions_url() {
+        let provider = FakeProvider::new(r#"["rust", ["rust lang", "rust book", "rust lang"]]"#);
+        let engine = get_engine(Some("https://example.com/suggest?q=%s"));
+
+        let suggestions = provider.get_suggestions(&engine, "rust b").unwrap();
+
+        assert_eq!(suggestions, vec!["rust lang", "rust book"]);
+        assert_eq!(
+            *provider.urls.borrow(),
+            vec!["https://example.com/suggest?q=rust%20b"]
+        );
+    }
+
+    #[test]
+    fn provider_is_not_asked_without_a_query_or_suggestions_url() {
+        let provider = FakeProvider::new(r#"["a"]"#);
+
+        let with_url = get_engine(Some("https://example.com/suggest?q=%s"));
+        assert!(provider
+            .get_suggestions(&with_url, " \t")
+            .unwrap()
+            .is_empty());
+
+        let without_url = get_engine(None);
+        assert!(provider
+            .get_suggestions(&without_url, "a")
+            .unwrap()
+            .is_empty());
+
+        assert!(provider.urls.borrow().is_empty());
+    }
+
+    #[test]
+    fn provider_limit_applies_to_the_suggestions() {
+        let mut provider = FakeProvider::new(r#"["a", "b", "c", "d"]"#);
+        provider.limit = 2;
+        let engine = get_engine(Some("https://example.com/suggest?q=%s"));
+
+        assert_eq!(
+            provider.get_suggestions(&engine, "x").unwrap(),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn suggestion_results_search_the_engine() {
+        let provider = FakeProvider::new(r#"[{"phrase": "cat food"}, {"other": 1}, 3]"#);
+        let engine = get_engine(Some("https://example.com/suggest?q=%s"));
+
+        let results = provider.get_suggestion_results(&engine, "cat").unwrap();
+        assert_eq!(results.len(), 1);
+
+        let result = results[0].text.to_owned().unwrap();
+        assert_eq!(result.text, "cat food");
+        assert_eq!(result.icon.as_deref(), Some("/tmp/engine.svg"));
+        assert_eq!(result.tint.as_deref(), Some("accent"));
+        assert_eq!(
+            result.action.open_url.unwrap().url,
+            "https://example.com/search?q=cat%20food"
+        );
+    }
+
+    #[test]
+    fn unexpected_bodies_are_decode_errors() {
+        for body in ["{}", "not json", "\"text\""] {
+            assert!(matches!(
+                parse_suggestions(body),
+                Err(WhiskersError::Decode(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn http_provider_reads_the_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            // The rest of the request has to be read before answering
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            let body = r#"["caf", ["café", "cafeteria", "caffeine"]]"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request_line
+        });
+
+        let engine = get_engine(Some(&format!("http://127.0.0.1:{}/complete?q=%s", port)));
+        let suggestions = HttpSuggestionProvider::new()
+            .limit(2)
+            .get_suggestions(&engine, "caf é")
+            .unwrap();
+
+        assert_eq!(suggestions, vec!["café", "cafeteria"]);
+        assert_eq!(
+            server.join().unwrap().trim_end(),
+            "GET /complete?q=caf%20%C3%A9 HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn http_provider_reports_refused_connections() {
+        // Binding and dropping the listener leaves a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let engine = get_engine(Some(&format!("http://127.0.0.1:{}/complete?q=%s", port)));
+
+        assert!(HttpSuggestionProvider::new()
+            .get_suggestions(&engine, "a")
+            .is_err());
+        assert!(get_suggestion_results(&engine, "a").is_empty());
+    }
+}
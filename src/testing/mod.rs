@@ -0,0 +1,35 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{Mutex, MutexGuard},
+};
+
+// Every path comes from HOME so the tests that touch files take turns
+static HOME_LOCK: Mutex<()> = Mutex::new(());
+
+// An empty HOME for one test, removed when dropped
+pub(crate) struct TestHome {
+    pub path: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Drop for TestHome {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+pub(crate) fn set_test_home(name: &str) -> TestHome {
+    let guard = HOME_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let path = env::temp_dir().join(format!("whiskers-test-{}-{}", name, process::id()));
+
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).expect("Error creating test home");
+    env::set_var("HOME", &path);
+
+    TestHome {
+        path,
+        _guard: guard,
+    }
+}
@@ -0,0 +1,155 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    pub fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha: 255,
+        }
+    }
+
+    // Reads #RRGGBB and #RRGGBBAA
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#')?;
+
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+
+        Some(Self {
+            red: channel(0)?,
+            green: channel(2)?,
+            blue: channel(4)?,
+            alpha: if hex.len() == 8 { channel(6)? } else { 255 },
+        })
+    }
+
+    // The alpha is only written when the color isn't opaque
+    pub fn to_hex(&self) -> String {
+        if self.alpha == 255 {
+            format!("#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)
+        } else {
+            format!(
+                "#{:02X}{:02X}{:02X}{:02X}",
+                self.red, self.green, self.blue, self.alpha
+            )
+        }
+    }
+
+    // The WCAG 2.1 relative luminance, from 0 for black to 1 for white
+    pub fn get_luminance(&self) -> f64 {
+        let linear = |channel: u8| {
+            let channel = channel as f64 / 255.0;
+
+            if channel <= 0.03928 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        0.2126 * linear(self.red) + 0.7152 * linear(self.green) + 0.0722 * linear(self.blue)
+    }
+
+    // The opaque color seen when this color is drawn on top of the background
+    pub fn blend_over(&self, background: &Color) -> Color {
+        let alpha = self.alpha as f64 / 255.0;
+        let blend = |foreground: u8, background: u8| {
+            (foreground as f64 * alpha + background as f64 * (1.0 - alpha)).round() as u8
+        };
+
+        Color::new(
+            blend(self.red, background.red),
+            blend(self.green, background.green),
+            blend(self.blue, background.blue),
+        )
+    }
+
+    // Hue in degrees, saturation and lightness from 0 to 1
+    pub fn to_hsl(&self) -> (f64, f64, f64) {
+        let red = self.red as f64 / 255.0;
+        let green = self.green as f64 / 255.0;
+        let blue = self.blue as f64 / 255.0;
+
+        let max = red.max(green).max(blue);
+        let min = red.min(green).min(blue);
+        let lightness = (max + min) / 2.0;
+        let delta = max - min;
+
+        if delta == 0.0 {
+            return (0.0, 0.0, lightness);
+        }
+
+        let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+
+        let hue = if max == red {
+            60.0 * (((green - blue) / delta).rem_euclid(6.0))
+        } else if max == green {
+            60.0 * ((blue - red) / delta + 2.0)
+        } else {
+            60.0 * ((red - green) / delta + 4.0)
+        };
+
+        (hue, saturation, lightness)
+    }
+
+    pub fn from_hsl(hue: f64, saturation: f64, lightness: f64) -> Self {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (hue.rem_euclid(2.0) - 1.0).abs());
+
+        let (red, green, blue) = match hue as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        let offset = lightness - chroma / 2.0;
+        let channel = |value: f64| ((value + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+        Color::new(channel(red), channel(green), channel(blue))
+    }
+
+    // Keeps the hue, saturation and alpha
+    pub fn with_lightness(&self, lightness: f64) -> Self {
+        let (hue, saturation, _) = self.to_hsl();
+
+        Self {
+            alpha: self.alpha,
+            ..Color::from_hsl(hue, saturation, lightness)
+        }
+    }
+}
+
+// The WCAG 2.1 contrast ratio, from 1 to 21. A translucent foreground is blended over the
+// background first.
+pub fn get_contrast_ratio(foreground: &Color, background: &Color) -> f64 {
+    let background = Color {
+        alpha: 255,
+        ..*background
+    };
+
+    let foreground_luminance = foreground.blend_over(&background).get_luminance();
+    let background_luminance = background.get_luminance();
+
+    let lighter = foreground_luminance.max(background_luminance);
+    let darker = foreground_luminance.min(background_luminance);
+
+    (lighter + 0.05) / (darker + 0.05)
+}
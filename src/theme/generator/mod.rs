@@ -0,0 +1,284 @@
+#[cfg(feature = "theme-images")]
+use std::{cmp::Ordering, collections::HashMap, path::Path};
+
+#[cfg(feature = "theme-images")]
+use image::{imageops::FilterType, ImageError};
+
+#[cfg(feature = "theme-images")]
+use crate::error::{decode_error, WhiskersError};
+
+use crate::settings::Theme;
+
+use super::{
+    color::{get_contrast_ratio, Color},
+    fix_contrast, get_contrast_fix, AA_UI_CONTRAST,
+};
+
+// Images are scaled down before counting colors, the palette doesn't need every pixel
+#[cfg(feature = "theme-images")]
+const PALETTE_IMAGE_SIZE: u32 = 96;
+#[cfg(feature = "theme-images")]
+const DEFAULT_PALETTE_SIZE: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeMode {
+    Dark,
+    Light,
+}
+
+// Lightness of each generated color in the dark and light modes
+struct ModeLightness {
+    background: f64,
+    secondary: f64,
+    tertiary: f64,
+    text: f64,
+    sub_text: f64,
+    warning: f64,
+    danger: f64,
+}
+
+impl ThemeMode {
+    fn get_lightness(&self) -> ModeLightness {
+        match self {
+            ThemeMode::Dark => ModeLightness {
+                background: 0.03,
+                secondary: 0.045,
+                tertiary: 0.06,
+                text: 0.94,
+                sub_text: 0.84,
+                warning: 0.71,
+                danger: 0.74,
+            },
+            ThemeMode::Light => ModeLightness {
+                background: 0.97,
+                secondary: 0.93,
+                tertiary: 0.89,
+                text: 0.08,
+                sub_text: 0.25,
+                warning: 0.35,
+                danger: 0.42,
+            },
+        }
+    }
+}
+
+// Derives every color from the hue and saturation of the seed. The seed is the accent, only
+// changing its lightness when it doesn't stand out from the background, and the result is
+// nudged until it passes the contrast audit.
+pub fn generate_theme(seed: &Color, mode: ThemeMode) -> Theme {
+    let (hue, saturation, _) = seed.to_hsl();
+    let lightness = mode.get_lightness();
+    let color = |saturation: f64, lightness: f64| Color::from_hsl(hue, saturation, lightness);
+
+    // Light backgrounds look washed out with a lot of saturation
+    let background_saturation = match mode {
+        ThemeMode::Dark => saturation,
+        ThemeMode::Light => saturation * 0.4,
+    };
+
+    let background = color(background_saturation, lightness.background);
+    let seed = Color {
+        alpha: 255,
+        ..*seed
+    };
+    let accent = get_contrast_fix(&seed, &background, AA_UI_CONTRAST).unwrap_or(seed);
+    let danger = Color::from_hsl(7.0, 0.9, lightness.danger);
+
+    let theme = Theme {
+        background: background.to_hex(),
+        secondary: color(background_saturation, lightness.secondary).to_hex(),
+        tertiary: color(background_saturation, lightness.tertiary).to_hex(),
+        accent: accent.to_hex(),
+        warning: Color::from_hsl(29.0, 0.9, lightness.warning).to_hex(),
+        danger: danger.to_hex(),
+        on_accent: get_on_color(hue, saturation, &accent).to_hex(),
+        on_danger: get_on_color(hue, saturation, &danger).to_hex(),
+        text: color(saturation, lightness.text).to_hex(),
+        sub_text: color(saturation * 0.4, lightness.sub_text).to_hex(),
+    };
+
+    fix_contrast(&theme)
+}
+
+// Uses the most colorful of the dominant colors of the image as the seed
+#[cfg(feature = "theme-images")]
+pub fn try_generate_theme_from_image(
+    path: impl AsRef<Path>,
+    mode: ThemeMode,
+) -> Result<Theme, WhiskersError> {
+    let palette = try_get_image_palette(path, DEFAULT_PALETTE_SIZE)?;
+
+    let seed = palette
+        .iter()
+        .enumerate()
+        .max_by(|(index, color), (other_index, other_color)| {
+            get_seed_score(*index, color)
+                .partial_cmp(&get_seed_score(*other_index, other_color))
+                .unwrap_or(Ordering::Equal)
+        })
+        .map(|(_, color)| *color)
+        .ok_or(WhiskersError::Decode(String::from(
+            "The image has no opaque pixels",
+        )))?;
+
+    Ok(generate_theme(&seed, mode))
+}
+
+#[cfg(feature = "theme-images")]
+pub fn generate_theme_from_image(path: impl AsRef<Path>, mode: ThemeMode) -> Theme {
+    try_generate_theme_from_image(path, mode).expect("Error generating theme from image")
+}
+
+// The dominant colors of the image, most common first. Similar colors are grouped together and
+// averaged, translucent pixels are ignored.
+#[cfg(feature = "theme-images")]
+pub fn try_get_image_palette(
+    path: impl AsRef<Path>,
+    size: usize,
+) -> Result<Vec<Color>, WhiskersError> {
+    let image = image::open(path)
+        .map_err(|error| match error {
+            ImageError::IoError(error) => WhiskersError::Io(error),
+            error => decode_error(error),
+        })?
+        .resize(PALETTE_IMAGE_SIZE, PALETTE_IMAGE_SIZE, FilterType::Triangle)
+        .to_rgba8();
+
+    // Counts and channel sums of each group, grouped by the 4 high bits of every channel
+    let mut groups = HashMap::<(u8, u8, u8), (u64, [u64; 3])>::new();
+
+    for pixel in image.pixels() {
+        let [red, green, blue, alpha] = pixel.0;
+
+        if alpha < 128 {
+            continue;
+        }
+
+        let group = groups
+            .entry((red >> 4, green >> 4, blue >> 4))
+            .or_insert((0, [0; 3]));
+
+        group.0 += 1;
+        group.1[0] += red as u64;
+        group.1[1] += green as u64;
+        group.1[2] += blue as u64;
+    }
+
+    let mut groups: Vec<(u64, [u64; 3])> = groups.into_values().collect();
+    groups.sort_by(|(count, sums), (other_count, other_sums)| {
+        other_count.cmp(count).then(sums.cmp(other_sums))
+    });
+
+    Ok(groups
+        .into_iter()
+        .take(size)
+        .map(|(count, sums)| {
+            Color::new(
+                (sums[0] / count) as u8,
+                (sums[1] / count) as u8,
+                (sums[2] / count) as u8,
+            )
+        })
+        .collect())
+}
+
+#[cfg(feature = "theme-images")]
+pub fn get_image_palette(path: impl AsRef<Path>, size: usize) -> Vec<Color> {
+    try_get_image_palette(path, size).expect("Error getting image palette")
+}
+
+// A dark or light shade of the seed hue, whichever reads better on the color
+fn get_on_color(hue: f64, saturation: f64, color: &Color) -> Color {
+    let dark = Color::from_hsl(hue, saturation, 0.05);
+    let light = Color::from_hsl(hue, saturation, 0.97);
+
+    if get_contrast_ratio(&dark, color) >= get_contrast_ratio(&light, color) {
+        dark
+    } else {
+        light
+    }
+}
+
+// Prefers saturated colors that aren't close to black or white, then the more common ones
+#[cfg(feature = "theme-images")]
+fn get_seed_score(index: usize, color: &Color) -> f64 {
+    let (_, saturation, lightness) = color.to_hsl();
+    let lightness_weight = 1.0 - (lightness - 0.5).abs() * 2.0;
+
+    (0.1 + saturation) * lightness_weight * (1.0 - index as f64 * 0.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::audit;
+
+    use super::*;
+
+    const SEEDS: [&str; 8] = [
+        "#808080", "#000000", "#FFFFFF", "#FF0000", "#00FF00", "#0000FF", "#FFFF00", "#6C3FC4",
+    ];
+
+    #[test]
+    fn generated_themes_pass_the_audit() {
+        for seed in SEEDS {
+            for mode in [ThemeMode::Dark, ThemeMode::Light] {
+                let theme = generate_theme(&Color::from_hex(seed).unwrap(), mode);
+
+                assert!(theme.validate().is_ok(), "{} {:?}", seed, mode);
+                assert_eq!(audit(&theme), Vec::new(), "{} {:?}", seed, mode);
+            }
+        }
+    }
+
+    #[test]
+    fn modes_have_dark_and_light_backgrounds() {
+        let seed = Color::from_hex("#6C3FC4").unwrap();
+
+        let dark = Color::from_hex(&generate_theme(&seed, ThemeMode::Dark).background).unwrap();
+        let light = Color::from_hex(&generate_theme(&seed, ThemeMode::Light).background).unwrap();
+
+        assert!(dark.get_luminance() < 0.05);
+        assert!(light.get_luminance() > 0.8);
+    }
+
+    #[test]
+    fn translucent_seed_gives_an_opaque_accent() {
+        let seed = Color::from_hex("#FF000080").unwrap();
+        let theme = generate_theme(&seed, ThemeMode::Dark);
+
+        assert_eq!(Color::from_hex(&theme.accent).unwrap().alpha, 255);
+    }
+
+    #[cfg(feature = "theme-images")]
+    #[test]
+    fn palette_has_the_most_common_colors_first() {
+        use image::{Rgba, RgbaImage};
+
+        let dir =
+            std::env::temp_dir().join(format!("whiskers-palette-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("palette.png");
+
+        // Three quarters red, a quarter blue and transparent green rows that are ignored
+        let image = RgbaImage::from_fn(96, 96, |x, y| match (x, y) {
+            (_, y) if y >= 80 => Rgba([0, 255, 0, 0]),
+            (x, _) if x < 72 => Rgba([220, 20, 20, 255]),
+            _ => Rgba([20, 20, 220, 255]),
+        });
+        image.save(&path).unwrap();
+
+        let palette = try_get_image_palette(&path, 5).unwrap();
+        let theme = try_generate_theme_from_image(&path, ThemeMode::Dark).unwrap();
+        let missing = try_get_image_palette(dir.join("missing.png"), 5);
+
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // The scaling blends the edges so there can be a few more colors after these two
+        assert!(palette.len() >= 2);
+        assert!(palette[0].red > 200 && palette[0].blue < 40);
+        assert!(palette[1].blue > 200 && palette[1].red < 40);
+        assert!(palette.iter().all(|color| color.green < 40));
+        assert_eq!(audit(&theme), Vec::new());
+        assert!(matches!(missing, Err(WhiskersError::Io(_))));
+    }
+}
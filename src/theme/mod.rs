@@ -0,0 +1,608 @@
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api::{settings::try_update_settings, store::read_store_source},
+    error::{decode_error, encode_error, WhiskersError},
+    paths::get_themes_store_path,
+    settings::Theme,
+    storage::{lock_exclusive, lock_shared, write_atomic},
+};
+
+use self::color::{get_contrast_ratio, Color};
+
+pub mod color;
+pub mod generator;
+
+pub const THEME_FORMAT: &str = "whiskers-launcher-theme";
+pub const THEME_FORMAT_VERSION: u16 = 1;
+
+// WCAG 2.1 AA minimums for text and for large text or UI parts like icons
+pub const AA_TEXT_CONTRAST: f64 = 4.5;
+pub const AA_UI_CONTRAST: f64 = 3.0;
+
+// Lightness steps tried when looking for a fix
+const LIGHTNESS_STEP: f64 = 0.005;
+const MAX_FIX_PASSES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreTheme {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    pub theme: Theme,
+}
+
+// The portable format themes are shared in. The format and version let other apps recognize the
+// file and let the colors change in the future.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ThemeFile {
+    format: String,
+    version: u16,
+    colors: Theme,
+}
+
+// A foreground color that doesn't have enough contrast on its background. The suggestion is the
+// foreground with the closest lightness that passes, when there's one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContrastIssue {
+    pub foreground: String,
+    pub background: String,
+    pub ratio: f64,
+    pub required: f64,
+    pub suggestion: Option<String>,
+}
+
+impl Theme {
+    pub fn get_colors(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("background", &self.background),
+            ("secondary", &self.secondary),
+            ("tertiary", &self.tertiary),
+            ("accent", &self.accent),
+            ("warning", &self.warning),
+            ("danger", &self.danger),
+            ("on_accent", &self.on_accent),
+            ("on_danger", &self.on_danger),
+            ("text", &self.text),
+            ("sub_text", &self.sub_text),
+        ]
+    }
+
+    pub fn set_color(&mut self, name: &str, color: impl Into<String>) {
+        let color = color.into();
+
+        match name {
+            "background" => self.background = color,
+            "secondary" => self.secondary = color,
+            "tertiary" => self.tertiary = color,
+            "accent" => self.accent = color,
+            "warning" => self.warning = color,
+            "danger" => self.danger = color,
+            "on_accent" => self.on_accent = color,
+            "on_danger" => self.on_danger = color,
+            "text" => self.text = color,
+            "sub_text" => self.sub_text = color,
+            _ => {}
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), WhiskersError> {
+        match self
+            .get_colors()
+            .into_iter()
+            .find(|(_, color)| !is_valid_color(color))
+        {
+            Some((name, color)) => Err(WhiskersError::InvalidColor {
+                name: name.to_owned(),
+                color: color.to_owned(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+// A #RRGGBB or #RRGGBBAA hex color
+pub fn is_valid_color(color: &str) -> bool {
+    Color::from_hex(color).is_some()
+}
+
+// The foreground and background pairs the launcher draws, with the contrast each one needs
+pub fn get_contrast_pairs() -> Vec<(&'static str, &'static str, f64)> {
+    let mut pairs = Vec::new();
+
+    for background in ["background", "secondary", "tertiary"] {
+        pairs.push(("text", background, AA_TEXT_CONTRAST));
+        pairs.push(("sub_text", background, AA_TEXT_CONTRAST));
+    }
+
+    pairs.push(("on_accent", "accent", AA_TEXT_CONTRAST));
+    pairs.push(("on_danger", "danger", AA_TEXT_CONTRAST));
+
+    for foreground in ["accent", "warning", "danger"] {
+        pairs.push((foreground, "background", AA_UI_CONTRAST));
+    }
+
+    pairs
+}
+
+// Checks every pair against WCAG 2.1 AA. Pairs with an invalid color are left to validate.
+pub fn audit(theme: &Theme) -> Vec<ContrastIssue> {
+    let colors = theme.get_colors();
+    let get_color = |name: &str| {
+        colors
+            .iter()
+            .find(|(color_name, _)| *color_name == name)
+            .and_then(|(_, color)| Color::from_hex(color))
+    };
+
+    let mut issues = Vec::new();
+
+    for (foreground_name, background_name, required) in get_contrast_pairs() {
+        let (foreground, background) =
+            match (get_color(foreground_name), get_color(background_name)) {
+                (Some(foreground), Some(background)) => (foreground, background),
+                _ => continue,
+            };
+
+        let ratio = get_contrast_ratio(&foreground, &background);
+
+        if ratio < required {
+            issues.push(ContrastIssue {
+                foreground: foreground_name.to_owned(),
+                background: background_name.to_owned(),
+                ratio,
+                required,
+                suggestion: get_contrast_fix(&foreground, &background, required)
+                    .map(|color| color.to_hex()),
+            });
+        }
+    }
+
+    issues
+}
+
+// Moves the foreground lightness up or down, whichever passes with the smaller change. A
+// translucent foreground that can't pass is made opaque.
+pub fn get_contrast_fix(foreground: &Color, background: &Color, required: f64) -> Option<Color> {
+    if get_contrast_ratio(foreground, background) >= required {
+        return Some(*foreground);
+    }
+
+    let (_, _, lightness) = foreground.to_hsl();
+    let mut offset = LIGHTNESS_STEP;
+
+    while offset < 1.0 + LIGHTNESS_STEP {
+        for candidate in [lightness - offset, lightness + offset] {
+            let color = foreground.with_lightness(candidate.clamp(0.0, 1.0));
+
+            if get_contrast_ratio(&color, background) >= required {
+                return Some(color);
+            }
+        }
+
+        offset += LIGHTNESS_STEP;
+    }
+
+    if foreground.alpha != 255 {
+        let opaque = Color {
+            alpha: 255,
+            ..*foreground
+        };
+
+        return get_contrast_fix(&opaque, background, required);
+    }
+
+    None
+}
+
+// Applies the suggestions until the theme passes. A color fixed for one background can fail on
+// another so it takes a few passes.
+pub fn fix_contrast(theme: &Theme) -> Theme {
+    let mut theme = theme.to_owned();
+
+    for _ in 0..MAX_FIX_PASSES {
+        let issues = audit(&theme);
+
+        if issues.iter().all(|issue| issue.suggestion.is_none()) {
+            break;
+        }
+
+        for issue in issues {
+            if let Some(suggestion) = issue.suggestion {
+                theme.set_color(&issue.foreground, suggestion);
+            }
+        }
+    }
+
+    theme
+}
+
+// Only valid themes are exported so every exported file can be imported again
+pub fn try_export_theme(theme: &Theme) -> Result<String, WhiskersError> {
+    theme.validate()?;
+
+    let file = ThemeFile {
+        format: String::from(THEME_FORMAT),
+        version: THEME_FORMAT_VERSION,
+        colors: theme.to_owned(),
+    };
+
+    serde_json::to_string_pretty(&file).map_err(encode_error)
+}
+
+pub fn export_theme(theme: &Theme) -> String {
+    try_export_theme(theme).expect("Error exporting theme")
+}
+
+// Reads an exported theme. A plain object with the colors, like the themes in the store, is
+// accepted as well.
+pub fn try_import_theme(json: &str) -> Result<Theme, WhiskersError> {
+    let value: Value = serde_json::from_str(json).map_err(decode_error)?;
+
+    let theme: Theme = match value.get("format") {
+        Some(format) => {
+            if format.as_str() != Some(THEME_FORMAT) {
+                return Err(WhiskersError::Decode(format!(
+                    "Unknown theme format {}",
+                    format
+                )));
+            }
+
+            let file: ThemeFile = serde_json::from_value(value.to_owned()).map_err(decode_error)?;
+
+            if file.version > THEME_FORMAT_VERSION {
+                return Err(WhiskersError::Decode(format!(
+                    "Unsupported theme format version {} (supported up to {})",
+                    file.version, THEME_FORMAT_VERSION
+                )));
+            }
+
+            file.colors
+        }
+        None => serde_json::from_value(value).map_err(decode_error)?,
+    };
+
+    theme.validate()?;
+    Ok(theme)
+}
+
+pub fn import_theme(json: &str) -> Theme {
+    try_import_theme(json).expect("Error importing theme")
+}
+
+// Parses the catalog, checking that the ids are unique and that every color is valid
+pub fn parse_themes_store(json: &str) -> Result<Vec<StoreTheme>, WhiskersError> {
+    let themes: Vec<StoreTheme> = serde_json::from_str(json).map_err(decode_error)?;
+
+    for (index, store_theme) in themes.iter().enumerate() {
+        if store_theme.id.trim().is_empty() {
+            return Err(WhiskersError::Decode(format!(
+                "Invalid store theme \"{}\": the id is empty",
+                store_theme.name
+            )));
+        }
+
+        if themes[..index]
+            .iter()
+            .any(|other| other.id == store_theme.id)
+        {
+            return Err(WhiskersError::Decode(format!(
+                "Invalid store theme \"{}\": the id is used by another theme",
+                store_theme.id
+            )));
+        }
+
+        store_theme.theme.validate()?;
+    }
+
+    Ok(themes)
+}
+
+pub fn try_get_store_themes() -> Result<Vec<StoreTheme>, WhiskersError> {
+    let path = get_themes_store_path();
+    let _lock = lock_shared(&path)?;
+
+    match fs::read_to_string(&path) {
+        Ok(json) => parse_themes_store(&json),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn get_store_themes() -> Vec<StoreTheme> {
+    try_get_store_themes().expect("Error getting store themes")
+}
+
+// Same sources as the extensions store: an http(s) or file:// url or a local path
+pub fn try_update_themes_store(
+    source: impl Into<String>,
+) -> Result<Vec<StoreTheme>, WhiskersError> {
+    let json = read_store_source(&source.into())?;
+    let themes = parse_themes_store(&json)?;
+
+    let path = get_themes_store_path();
+    let _lock = lock_exclusive(&path)?;
+    write_atomic(&path, json)?;
+
+    Ok(themes)
+}
+
+pub fn update_themes_store(source: impl Into<String>) -> Vec<StoreTheme> {
+    try_update_themes_store(source).expect("Error updating themes store")
+}
+
+// Sets the store theme with the id as the launcher theme
+pub fn try_apply_theme(id: impl Into<String>) -> Result<Theme, WhiskersError> {
+    let id = id.into();
+
+    let theme = try_get_store_themes()?
+        .into_iter()
+        .find(|store_theme| store_theme.id == id)
+        .map(|store_theme| store_theme.theme)
+        .ok_or(WhiskersError::MissingTheme(id))?;
+
+    try_set_theme(theme.to_owned())?;
+    Ok(theme)
+}
+
+pub fn apply_theme(id: impl Into<String>) -> Theme {
+    try_apply_theme(id).expect("Error applying theme")
+}
+
+// Sets any valid theme, like an imported one, as the launcher theme
+pub fn try_set_theme(theme: Theme) -> Result<(), WhiskersError> {
+    theme.validate()?;
+    try_update_settings(|settings| settings.theme = theme)?;
+    Ok(())
+}
+
+pub fn set_theme(theme: Theme) {
+    try_set_theme(theme).expect("Error setting theme");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::settings::try_get_settings, settings::get_default_settings, testing::set_test_home,
+    };
+
+    use super::*;
+
+    fn get_color(hex: &str) -> Color {
+        Color::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn default_theme_passes_the_audit() {
+        let theme = get_default_settings().theme;
+
+        assert!(theme.validate().is_ok());
+        assert_eq!(audit(&theme), Vec::new());
+    }
+
+    #[test]
+    fn fixing_the_default_theme_changes_nothing() {
+        let theme = get_default_settings().theme;
+
+        assert_eq!(fix_contrast(&theme).get_colors(), theme.get_colors());
+    }
+
+    #[test]
+    fn fixing_a_poor_default_theme_passes_the_audit() {
+        let mut theme = get_default_settings().theme;
+        theme.text = String::from("#555555");
+        theme.on_accent = theme.accent.to_owned();
+        theme.danger = theme.background.to_owned();
+
+        let issues = audit(&theme);
+        let failing: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.foreground.as_str(), issue.background.as_str()))
+            .collect();
+
+        assert!(failing.contains(&("text", "background")));
+        assert!(failing.contains(&("on_accent", "accent")));
+        assert!(failing.contains(&("danger", "background")));
+        assert!(issues.iter().all(|issue| issue.ratio < issue.required));
+
+        let fixed = fix_contrast(&theme);
+        assert_eq!(audit(&fixed), Vec::new());
+
+        // Only the failing foregrounds change
+        assert_eq!(fixed.background, theme.background);
+        assert_eq!(fixed.accent, theme.accent);
+        assert_eq!(fixed.sub_text, theme.sub_text);
+    }
+
+    #[test]
+    fn contrast_fix_is_the_closest_passing_lightness() {
+        let foreground = get_color("#777777");
+        let background = get_color("#FFFFFF");
+
+        assert!(get_contrast_ratio(&foreground, &background) < AA_TEXT_CONTRAST);
+
+        let fix = get_contrast_fix(&foreground, &background, AA_TEXT_CONTRAST).unwrap();
+        let (_, _, fix_lightness) = fix.to_hsl();
+        let (_, _, lightness) = foreground.to_hsl();
+
+        assert!(get_contrast_ratio(&fix, &background) >= AA_TEXT_CONTRAST);
+        assert!(fix_lightness < lightness);
+        assert!(lightness - fix_lightness <= 0.02);
+
+        // A passing color is kept as it is
+        assert_eq!(
+            get_contrast_fix(&fix, &background, AA_TEXT_CONTRAST),
+            Some(fix)
+        );
+    }
+
+    #[test]
+    fn translucent_foreground_keeps_its_alpha_when_it_can_pass() {
+        let foreground = get_color("#00000080");
+        let background = get_color("#FFFFFF");
+
+        let fix = get_contrast_fix(&foreground, &background, AA_UI_CONTRAST).unwrap();
+
+        assert_eq!(fix.alpha, 0x80);
+        assert!(get_contrast_ratio(&fix, &background) >= AA_UI_CONTRAST);
+    }
+
+    #[test]
+    fn translucent_foreground_is_made_opaque_when_it_cant_pass() {
+        // Even black at half opacity is a mid grey on white, which isn't enough for text
+        let foreground = get_color("#FFFFFF80");
+        let background = get_color("#FFFFFF");
+
+        assert!(get_contrast_ratio(&get_color("#00000080"), &background) < AA_TEXT_CONTRAST);
+
+        let fix = get_contrast_fix(&foreground, &background, AA_TEXT_CONTRAST).unwrap();
+
+        assert_eq!(fix.alpha, 255);
+        assert!(get_contrast_ratio(&fix, &background) >= AA_TEXT_CONTRAST);
+    }
+
+    #[test]
+    fn impossible_contrast_has_no_fix() {
+        let foreground = get_color("#777777");
+        let background = get_color("#777777");
+
+        assert_eq!(get_contrast_fix(&foreground, &background, 22.0), None);
+
+        let mut theme = get_default_settings().theme;
+        theme.text = String::from("not a color");
+        assert!(audit(&theme).iter().all(|issue| issue.foreground != "text"));
+    }
+
+    fn get_store_json(ids: &[&str]) -> String {
+        let theme = serde_json::to_string(&get_default_settings().theme).unwrap();
+
+        let themes: Vec<String> = ids
+            .iter()
+            .map(|id| format!(r#"{{"id": "{}", "name": "Theme", "theme": {}}}"#, id, theme))
+            .collect();
+
+        format!("[{}]", themes.join(","))
+    }
+
+    #[test]
+    fn exported_themes_import_again() {
+        let mut theme = get_default_settings().theme;
+        theme.accent = String::from("#12345680");
+
+        let json = try_export_theme(&theme).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["format"], THEME_FORMAT);
+        assert_eq!(value["version"], THEME_FORMAT_VERSION);
+
+        assert_eq!(
+            try_import_theme(&json).unwrap().get_colors(),
+            theme.get_colors()
+        );
+
+        // The plain colors object is read too
+        let plain = serde_json::to_string(&theme).unwrap();
+        assert_eq!(
+            try_import_theme(&plain).unwrap().get_colors(),
+            theme.get_colors()
+        );
+    }
+
+    #[test]
+    fn invalid_themes_are_not_exported() {
+        let mut theme = get_default_settings().theme;
+        theme.text = String::from("#FFF");
+
+        assert!(matches!(
+            try_export_theme(&theme),
+            Err(WhiskersError::InvalidColor { name, .. }) if name == "text"
+        ));
+    }
+
+    #[test]
+    fn foreign_and_newer_files_are_rejected() {
+        let json = export_theme(&get_default_settings().theme);
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+
+        value["format"] = Value::from("other-launcher-theme");
+        assert!(matches!(
+            try_import_theme(&value.to_string()),
+            Err(WhiskersError::Decode(_))
+        ));
+
+        value["format"] = Value::from(THEME_FORMAT);
+        value["version"] = Value::from(THEME_FORMAT_VERSION + 1);
+        assert!(matches!(
+            try_import_theme(&value.to_string()),
+            Err(WhiskersError::Decode(_))
+        ));
+
+        assert!(matches!(
+            try_import_theme("not json"),
+            Err(WhiskersError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_colors_are_rejected() {
+        for color in ["#FFF", "#GGGGGG", "FFFFFF", "#FFFFFFF", ""] {
+            let json = export_theme(&get_default_settings().theme);
+            let mut value: Value = serde_json::from_str(&json).unwrap();
+            value["colors"]["sub_text"] = Value::from(color);
+
+            assert!(
+                matches!(
+                    try_import_theme(&value.to_string()),
+                    Err(WhiskersError::InvalidColor { name, .. }) if name == "sub_text"
+                ),
+                "{}",
+                color
+            );
+        }
+    }
+
+    #[test]
+    fn store_ids_have_to_be_unique_and_not_empty() {
+        assert_eq!(
+            parse_themes_store(&get_store_json(&["a", "b"]))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        for ids in [&["a", "a"][..], &[" "][..], &[""][..]] {
+            assert!(
+                matches!(
+                    parse_themes_store(&get_store_json(ids)),
+                    Err(WhiskersError::Decode(_))
+                ),
+                "{:?}",
+                ids
+            );
+        }
+    }
+
+    #[test]
+    fn store_themes_are_applied_to_the_settings() {
+        let home = set_test_home("theme-store");
+        let store_path = home.path.join("themes.json");
+        fs::write(&store_path, get_store_json(&["dark"])).unwrap();
+
+        try_update_themes_store(format!("file://{}", store_path.display())).unwrap();
+        assert_eq!(try_get_store_themes().unwrap()[0].id, "dark");
+
+        let theme = try_apply_theme("dark").unwrap();
+        assert_eq!(
+            try_get_settings().unwrap().theme.get_colors(),
+            theme.get_colors()
+        );
+
+        assert!(matches!(
+            try_apply_theme("light"),
+            Err(WhiskersError::MissingTheme(id)) if id == "light"
+        ));
+    }
+}
@@ -1,4 +1,9 @@
-use std::{env, process::Command};
+use std::{
+    env,
+    process::Command,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use notify_rust::Notification;
@@ -7,38 +12,171 @@ use notify_rust::Notification;
 pub struct Search {
     pub keyword: Option<String>,
     pub search_text: String,
+    pub raw: String,
+    pub terms: Vec<String>,
+    pub phrases: Vec<String>,
+    pub flags: Vec<String>,
+    pub modifiers: Vec<SearchModifier>,
 }
 
-pub fn get_search(text: impl Into<String>) -> Search {
-    let text = text.into();
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchModifier {
+    pub key: String,
+    pub value: String,
+}
 
-    let mut keyword = "".to_string();
-    let mut search_text = "".to_string();
-    let mut has_keyword = false;
+impl Search {
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|search_flag| search_flag == flag)
+    }
 
-    for char in text.chars() {
-        if char == ' ' && !has_keyword {
-            has_keyword = true;
-        } else if !has_keyword {
-            keyword += &char.to_string();
-        } else {
-            search_text += &char.to_string();
-        }
+    // The last value wins when a modifier is given more than once
+    pub fn get_modifier(&self, key: &str) -> Option<&str> {
+        self.modifiers
+            .iter()
+            .rev()
+            .find(|modifier| modifier.key == key)
+            .map(|modifier| modifier.value.as_str())
     }
+}
 
-    if !has_keyword {
-        search_text = keyword.to_owned();
+struct SearchToken {
+    text: String,
+    quoted: bool,
+}
+
+// The keyword is everything before the first space and the search text everything after it.
+// The search text is then split into terms, "quoted phrases", --flags and key:value modifiers.
+pub fn get_search(text: impl Into<String>) -> Search {
+    let raw = text.into();
+
+    match raw.split_once(' ') {
+        Some((keyword, search_text)) => {
+            let keyword = Some(keyword.to_owned());
+            let search_text = search_text.trim().to_owned();
+            parse_search(raw, keyword, search_text)
+        }
+        None => {
+            let search_text = raw.trim().to_owned();
+            parse_search(raw, None, search_text)
+        }
     }
+}
+
+// Parses the whole text as search text, for queries that don't start with a known keyword
+pub fn get_search_without_keyword(text: impl Into<String>) -> Search {
+    let raw = text.into();
+    let search_text = raw.trim().to_owned();
+    parse_search(raw, None, search_text)
+}
 
-    search_text = search_text.trim().to_string();
+fn parse_search(raw: String, keyword: Option<String>, search_text: String) -> Search {
+    let mut terms = Vec::<String>::new();
+    let mut phrases = Vec::<String>::new();
+    let mut flags = Vec::<String>::new();
+    let mut modifiers = Vec::<SearchModifier>::new();
+
+    for token in get_search_tokens(&search_text) {
+        if token.quoted {
+            phrases.push(token.text);
+            continue;
+        }
+
+        if let Some(flag) = token.text.strip_prefix("--").filter(|flag| !flag.is_empty()) {
+            match flag.split_once('=') {
+                Some((key, value)) if !key.is_empty() => modifiers.push(SearchModifier {
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                }),
+                _ => flags.push(flag.to_owned()),
+            }
+
+            continue;
+        }
+
+        match get_modifier(&token.text) {
+            Some(modifier) => modifiers.push(modifier),
+            None => terms.push(token.text),
+        }
+    }
 
     Search {
-        keyword: match has_keyword {
-            true => Some(keyword),
-            false => None,
-        },
+        keyword,
         search_text,
+        raw,
+        terms,
+        phrases,
+        flags,
+        modifiers,
+    }
+}
+
+// Splits on whitespace keeping quoted text together. A token that starts with a quote is a
+// phrase and an unclosed quote runs until the end of the text.
+fn get_search_tokens(text: &str) -> Vec<SearchToken> {
+    let mut tokens = Vec::<SearchToken>::new();
+    let mut current: Option<SearchToken> = None;
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => {
+                in_quotes = !in_quotes;
+
+                current.get_or_insert(SearchToken {
+                    text: String::new(),
+                    quoted: true,
+                });
+            }
+            // Only quotes and backslashes are escaped so that paths like "C:\Users" keep working
+            '\\' if in_quotes && matches!(chars.peek(), Some('"') | Some('\\')) => {
+                if let (Some(token), Some(escaped)) = (current.as_mut(), chars.next()) {
+                    token.text.push(escaped);
+                }
+            }
+            char if char.is_whitespace() && !in_quotes => {
+                if let Some(token) = current.take() {
+                    tokens.push(token);
+                }
+            }
+            char => current
+                .get_or_insert(SearchToken {
+                    text: String::new(),
+                    quoted: false,
+                })
+                .text
+                .push(char),
+        }
     }
+
+    if let Some(token) = current {
+        tokens.push(token);
+    }
+
+    tokens
+        .into_iter()
+        .filter(|token| !token.text.is_empty())
+        .collect()
+}
+
+// Keys are made of letters, digits, "-" and "_" so that urls like https://... stay terms
+fn get_modifier(text: &str) -> Option<SearchModifier> {
+    let (key, value) = text.split_once(':')?;
+
+    let is_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|char| char.is_alphanumeric() || char == '-' || char == '_');
+
+    if !is_key || value.is_empty() || value.starts_with("//") {
+        return None;
+    }
+
+    Some(SearchModifier {
+        key: key.to_owned(),
+        value: value.to_owned(),
+    })
 }
 
 pub fn fuzzy_matches(original_text: impl Into<String>, search_text: impl Into<String>) -> bool {
@@ -47,16 +185,90 @@ pub fn fuzzy_matches(original_text: impl Into<String>, search_text: impl Into<St
         .is_some()
 }
 
+// The SkimMatcherV2 score of the match, None when the text doesn't match
+pub fn fuzzy_score(
+    original_text: impl Into<String>,
+    search_text: impl Into<String>,
+) -> Option<i64> {
+    SkimMatcherV2::default().fuzzy_match(&original_text.into(), &search_text.into())
+}
+
+// The score and the char indices of the text that matched
+pub fn fuzzy_indices(
+    original_text: impl Into<String>,
+    search_text: impl Into<String>,
+) -> Option<(i64, Vec<usize>)> {
+    SkimMatcherV2::default().fuzzy_indices(&original_text.into(), &search_text.into())
+}
+
+// Percent encodes everything but the RFC 3986 unreserved characters
+pub fn encode_uri_component(text: impl Into<String>) -> String {
+    let text = text.into();
+    let mut encoded = String::with_capacity(text.len());
+
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+// Expands the placeholders in a single pass so that a query containing a placeholder isn't
+// expanded again. %s and {searchTerms} are percent encoded, %S is inserted as typed and {lang} or
+// {language} are the language code.
+pub fn expand_query_template(template: &str, query: &str, lang: &str) -> String {
+    let encoded_query = encode_uri_component(query);
+    let encoded_lang = encode_uri_component(lang);
+
+    let placeholders = [
+        ("%s", encoded_query.as_str()),
+        ("%S", query),
+        ("{searchTerms}", encoded_query.as_str()),
+        ("{lang}", encoded_lang.as_str()),
+        ("{language}", encoded_lang.as_str()),
+    ];
+
+    let mut url = String::with_capacity(template.len() + encoded_query.len());
+    let mut rest = template;
+
+    'outer: while !rest.is_empty() {
+        for (placeholder, value) in placeholders {
+            if let Some(remaining) = rest.strip_prefix(placeholder) {
+                url.push_str(value);
+                rest = remaining;
+                continue 'outer;
+            }
+        }
+
+        let mut chars = rest.chars();
+
+        if let Some(char) = chars.next() {
+            url.push(char);
+        }
+
+        rest = chars.as_str();
+    }
+
+    url
+}
+
 pub fn send_notification(title: impl Into<String>, message: impl Into<String>) {
     let title = title.into();
     let message = message.into();
 
     if on_linux() {
-        Command::new("sh")
+        let mut child = Command::new("sh")
             .arg("-c")
             .arg(format!("notify-send {} {}", title, message))
             .spawn()
             .expect("Error sending notification");
+
+        // Reaps the process without making the caller wait for it
+        thread::spawn(move || child.wait());
     } else {
 
         #[cfg(target_os = "linux")]
@@ -80,6 +292,14 @@ pub fn send_notification(title: impl Into<String>, message: impl Into<String>) {
     }
 }
 
+// Seconds since the unix epoch, what the launch history and frecency store record
+pub fn get_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
 pub fn on_linux() -> bool {
     env::consts::OS == "linux"
 }
@@ -90,3 +310,136 @@ pub fn on_windows() -> bool {
 
 pub const FLAG_NO_WINDOW: u32 = 0x08000000;
 pub const FLAG_DETACHED_PROCESS: u32 = 0x00000008;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pieces the test inputs are built from, with the whitespace and non ASCII text that used to
+    // trip up splitting on the first space
+    const PIECES: [&str; 14] = [
+        "", " ", "  ", "\t", "\n", "\u{a0}", "\u{3000}", "é", "🐈", "\"", "--", "a:b", "s", "foo",
+    ];
+
+    // get_search before it parsed the search text
+    fn get_legacy_search(text: &str) -> (Option<String>, String) {
+        match text.split_once(' ') {
+            Some((keyword, search_text)) => {
+                (Some(keyword.to_owned()), search_text.trim().to_owned())
+            }
+            None => (None, text.trim().to_owned()),
+        }
+    }
+
+    fn get_inputs() -> Vec<String> {
+        let mut inputs = Vec::new();
+
+        for first in PIECES {
+            for second in PIECES {
+                for third in PIECES {
+                    inputs.push(format!("{}{}{}", first, second, third));
+                }
+            }
+        }
+
+        inputs
+    }
+
+    #[test]
+    fn keyword_and_search_text_are_unchanged() {
+        for input in get_inputs() {
+            let search = get_search(&input);
+
+            assert_eq!(
+                (search.keyword, search.search_text),
+                get_legacy_search(&input),
+                "{:?}",
+                input
+            );
+            assert_eq!(search.raw, input);
+        }
+    }
+
+    #[test]
+    fn only_a_plain_space_splits_the_keyword() {
+        let search = get_search("s\u{a0}foo");
+        assert_eq!(search.keyword, None);
+        assert_eq!(search.search_text, "s\u{a0}foo");
+
+        let search = get_search("s\tfoo bar");
+        assert_eq!(search.keyword.as_deref(), Some("s\tfoo"));
+        assert_eq!(search.terms, vec!["bar"]);
+
+        let search = get_search(" foo");
+        assert_eq!(search.keyword.as_deref(), Some(""));
+        assert_eq!(search.search_text, "foo");
+
+        let search = get_search("🐈 ünï cödé");
+        assert_eq!(search.keyword.as_deref(), Some("🐈"));
+        assert_eq!(search.terms, vec!["ünï", "cödé"]);
+    }
+
+    #[test]
+    fn parts_never_contain_unquoted_whitespace() {
+        for input in get_inputs() {
+            let search = get_search(&input);
+
+            let parts = search
+                .terms
+                .iter()
+                .chain(&search.flags)
+                .chain(search.modifiers.iter().map(|modifier| &modifier.key))
+                .chain(search.modifiers.iter().map(|modifier| &modifier.value));
+
+            for part in parts {
+                assert!(!part.is_empty(), "{:?}", input);
+                assert!(!part.contains(char::is_whitespace), "{:?}", input);
+            }
+
+            for phrase in &search.phrases {
+                assert!(!phrase.is_empty(), "{:?}", input);
+            }
+
+            if search.search_text.is_empty() {
+                assert!(
+                    search.terms.is_empty() && search.phrases.is_empty(),
+                    "{:?}",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unicode_whitespace_separates_terms() {
+        let search = get_search("s foo\u{3000}bar\u{a0}baz\tqux\nquux");
+
+        assert_eq!(search.terms, vec!["foo", "bar", "baz", "qux", "quux"]);
+    }
+
+    #[test]
+    fn quotes_keep_whitespace_in_phrases() {
+        let search = get_search("s \"two  words\" \"🐈\u{3000}cat\" \"unclosed  ");
+
+        // The search text is trimmed so an unclosed phrase loses its trailing whitespace
+        assert_eq!(
+            search.phrases,
+            vec!["two  words", "🐈\u{3000}cat", "unclosed"]
+        );
+        assert!(search.terms.is_empty());
+
+        let search = get_search("s \"say \\\"hi\\\"\" \"C:\\Users\"");
+        assert_eq!(search.phrases, vec!["say \"hi\"", "C:\\Users"]);
+    }
+
+    #[test]
+    fn flags_and_modifiers_are_split_from_terms() {
+        let search = get_search("f --hidden -- lang:pt --max=3 https://example.com :x é:ü");
+
+        assert!(search.has_flag("hidden"));
+        assert_eq!(search.get_modifier("lang"), Some("pt"));
+        assert_eq!(search.get_modifier("max"), Some("3"));
+        assert_eq!(search.get_modifier("é"), Some("ü"));
+        assert_eq!(search.terms, vec!["--", "https://example.com", ":x"]);
+    }
+}